@@ -1,11 +1,13 @@
-const NUM_CHARS: usize = 64;
-const SIXBIT2CHAR: [char; NUM_CHARS] = [
+use std::iter;
+
+pub const NUM_CHARS: usize = 64;
+pub const SIXBIT2CHAR: [char; NUM_CHARS] = [
     'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O', 'P', 'Q', 'R', 'S',
     'T', 'U', 'V', 'W', 'X', 'Y', 'Z', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l',
     'm', 'n', 'o', 'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', '0', '1', '2', '3', '4',
     '5', '6', '7', '8', '9', '+', '/',
 ];
-const PADDING: char = '=';
+pub const PADDING: char = '=';
 
 enum RemainderBits {
     Zero,
@@ -14,50 +16,54 @@ enum RemainderBits {
 }
 
 pub fn encode(bytes: &[u8]) -> String {
-    let encoded_size = (bytes.len() / 3 + usize::from(bytes.len() % 3 > 0)) * 4;
-    let mut encoded: Vec<char> = Vec::with_capacity(encoded_size);
+    encode_with(bytes, &SIXBIT2CHAR, PADDING)
+}
+
+// encode using a custom alphabet, indexed by six-bit value, and padding char
+pub fn encode_with(bytes: &[u8], alphabet: &[char; NUM_CHARS], padding: char) -> String {
+    let sixbits = encode_sixbits(bytes);
+    let num_padding = (4 - sixbits.len() % 4) % 4;
+    sixbits
+        .iter()
+        .map(|&sixbit| alphabet[usize::from(sixbit)])
+        .chain(iter::repeat_n(padding, num_padding))
+        .collect()
+}
+
+// the six-bit groups of bytes, each in [0, 63], before they are mapped to
+// chars; encode is these looked up in SIXBIT2CHAR, padded with PADDING to a
+// multiple of four chars
+pub fn encode_sixbits(bytes: &[u8]) -> Vec<u8> {
+    let mut sixbits: Vec<u8> = Vec::with_capacity(bytes.len().div_ceil(3) * 4);
 
-    let mut iter = bytes.iter();
     let (mut remainder, mut num_bits) = (0, RemainderBits::Zero);
-    while let Some(b) = iter.next() {
+    for b in bytes {
         (remainder, num_bits) = match num_bits {
             RemainderBits::Zero => {
-                let sixbit = (b & 0b11111100) >> 2;
-                encoded.push(SIXBIT2CHAR[usize::from(sixbit)]);
+                sixbits.push((b & 0b11111100) >> 2);
                 ((b & 0b00000011) << 4, RemainderBits::Two)
             }
             RemainderBits::Two => {
-                let sixbit = remainder | ((b & 0b11110000) >> 4);
-                encoded.push(SIXBIT2CHAR[usize::from(sixbit)]);
+                sixbits.push(remainder | ((b & 0b11110000) >> 4));
                 ((b & 0b00001111) << 2, RemainderBits::Four)
             }
             RemainderBits::Four => {
-                let sixbit = remainder | ((b & 0b11000000) >> 6);
-                encoded.push(SIXBIT2CHAR[usize::from(sixbit)]);
-                encoded.push(SIXBIT2CHAR[usize::from(b & 0b00111111)]);
+                sixbits.push(remainder | ((b & 0b11000000) >> 6));
+                sixbits.push(b & 0b00111111);
                 (0, RemainderBits::Zero)
             }
         }
     }
     match num_bits {
         RemainderBits::Zero => (),
-        RemainderBits::Two => {
-            encoded.push(SIXBIT2CHAR[usize::from(remainder)]);
-            encoded.push(PADDING);
-            encoded.push(PADDING);
-        }
-        RemainderBits::Four => {
-            encoded.push(SIXBIT2CHAR[usize::from(remainder)]);
-            encoded.push(PADDING);
-        }
+        RemainderBits::Two | RemainderBits::Four => sixbits.push(remainder),
     }
-
-    encoded.iter().collect()
+    sixbits
 }
 
 #[cfg(test)]
 mod tests {
-    use super::encode;
+    use super::{encode, encode_sixbits, encode_with, NUM_CHARS, PADDING, SIXBIT2CHAR};
 
     #[test]
     fn test_one() {
@@ -95,4 +101,38 @@ mod tests {
         assert_eq!(encode(&bytes2), "YWJQOXg0REZvdg==");
         assert_eq!(encode(&bytes3), "YWJQOXg0REZvdkg=");
     }
+
+    #[test]
+    fn test_custom_alphabet() {
+        let mut reversed = SIXBIT2CHAR;
+        reversed.reverse();
+        let bytes: [u8; 5] = [0b01001101, 0b01100001, 0b01101110, 0b00000000, 0b11111111];
+        let standard = encode(&bytes);
+        let expected: String = standard
+            .chars()
+            .map(|c| match SIXBIT2CHAR.iter().position(|s| *s == c) {
+                Some(i) => reversed[i],
+                None => '.',
+            })
+            .collect();
+        assert_eq!(encode_with(&bytes, &reversed, '.'), expected);
+        assert_eq!(encode_with(&bytes, &SIXBIT2CHAR, '='), standard);
+        assert_eq!(reversed.len(), NUM_CHARS);
+    }
+
+    #[test]
+    fn test_sixbits() {
+        for bytes in ["", "M", "Ma", "Man", "Many hands make light work."] {
+            let sixbits = encode_sixbits(bytes.as_bytes());
+            assert!(sixbits.iter().all(|&sixbit| sixbit < 64));
+            let mut chars: String = sixbits
+                .iter()
+                .map(|&sixbit| SIXBIT2CHAR[usize::from(sixbit)])
+                .collect();
+            while !chars.len().is_multiple_of(4) {
+                chars.push(PADDING);
+            }
+            assert_eq!(chars, encode(bytes.as_bytes()));
+        }
+    }
 }
@@ -1,99 +1,436 @@
-use std::path::PathBuf;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
 use std::result;
+use std::time::Instant;
 
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
 use thiserror::Error;
 
-use identicon::color;
+use identicon::{color, OutputFormat};
 
 const DEFAULT_SIZE: u32 = 60;
-const DEFAULT_BACKGROUND: color::RGB = color::RGB::new(240, 240, 240);
-const BACKGROUND_DELIMITER: &'static str = ",";
+const BORDER_DELIMITER: &str = ":";
+const TRANSPARENT_BACKGROUND: &str = "none";
+const DEFAULT_BENCH_COUNT: u32 = 1000;
+// the size, padding and border width must be less than this for the image
+// size in pixels to fit in u32
+const MAX_PIXELS: u32 = 613566757;
 
 #[derive(Parser)]
 /// This is an identicon generator.
 ///
 /// Input your name and a file path to save your identicon image.
 /// More customizing options are available. Use -h or --help for details.
-#[clap(author, version)]
+// the name isn't required by the hidden completions subcommand, so the usage
+// is spelled out to keep it out of the help
+#[clap(
+    author,
+    version,
+    subcommand_negates_reqs = true,
+    subcommand_precedence_over_arg = true,
+    override_usage = "cli [OPTIONS] <STRING>... <SUBCOMMAND>"
+)]
 struct Cli {
-    #[clap(value_parser, value_name = "STRING")]
-    /// Your name, or any random string
-    name: String,
+    #[clap(value_parser, value_name = "STRING", required = true)]
+    /// Your name, or any random string; several names render one file each into the path
+    names: Vec<String>,
 
-    #[clap(short, long, default_value_t = DEFAULT_SIZE, value_parser = clap::value_parser!(u32).range(..613566757), value_name = "U32")]
+    #[clap(short, long, default_value_t = DEFAULT_SIZE, value_parser = clap::value_parser!(u32).range(..i64::from(MAX_PIXELS)), value_name = "U32")]
     /// The number of pixels of each square in the generated identicon; must be less than 613566757 (image size in pixels must fit in u32)
     size: u32,
 
-    #[clap(short, long, default_value_t = DEFAULT_BACKGROUND, value_parser = parse_color, value_name="RGB")]
-    /// The background color in RGB format separated by ","; e.g. 255,0,0 (red)
-    background: color::RGB,
+    #[clap(short, long, env = "IDENTICON_BACKGROUND", default_value_t = Background::Color(identicon::DEFAULT_BACKGROUND), value_parser = parse_background, value_name="RGB")]
+    /// The background color as RGB separated by ",", hex or a CSS basic color name; e.g. 255,0,0, #ff0000 or red, or "none" for a transparent png or gif
+    background: Background,
+
+    #[clap(short, long, env = "IDENTICON_FOREGROUND", value_parser = parse_color, value_name = "RGB")]
+    /// The foreground color as RGB separated by ",", hex or a CSS basic color name; derived from the name if absent
+    foreground: Option<color::RGB>,
+
+    #[clap(short, long, default_value_t = 0, value_parser = clap::value_parser!(u32).range(..i64::from(MAX_PIXELS)), value_name = "U32")]
+    /// The number of background pixels added around the identicon; must be less than 613566757
+    padding: u32,
+
+    #[clap(long, value_parser = parse_border, value_name = "U32:RGB")]
+    /// The border width in pixels and its color separated by ":"; e.g. 5:0,0,0 (5px black); the width must be less than 613566757
+    border: Option<(u32, color::RGB)>,
+
+    #[clap(long, value_parser, value_name = "STRING")]
+    /// An extra value mixed into the hash, giving each namespace its own identicons
+    seed: Option<String>,
+
+    #[clap(long, global = true, value_parser)]
+    /// Print a JSON object with the dimensions, colors and outputs instead
+    json: bool,
 
     #[clap(subcommand)]
     command: Command,
 }
 
+#[derive(Clone)]
+enum Background {
+    Color(color::RGB),
+    Transparent,
+}
+
+impl fmt::Display for Background {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Color(color) => write!(f, "{}", color),
+            Self::Transparent => write!(f, "{}", TRANSPARENT_BACKGROUND),
+        }
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 enum ImageFormat {
-    PNG,
-    JPEG,
-    GIF,
+    Png,
+    Jpeg,
+    Gif,
+}
+
+impl From<ImageFormat> for OutputFormat {
+    fn from(format: ImageFormat) -> Self {
+        match format {
+            ImageFormat::Png => OutputFormat::Png,
+            ImageFormat::Jpeg => OutputFormat::Jpeg,
+            ImageFormat::Gif => OutputFormat::Gif,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum Shell {
+    Bash,
+    Zsh,
+    Fish,
 }
 
 #[derive(Subcommand)]
 enum Command {
     Render {
         #[clap(value_parser, value_name = "FILE")]
-        /// The output file path; the extension determins the image format
+        /// The output file path; the extension determins the image format unless --format is given
         path: PathBuf,
+
+        #[clap(short = 'F', long = "format", arg_enum, value_parser)]
+        /// Image formats to render, each written to the path with the format's extension; may be repeated
+        formats: Vec<ImageFormat>,
     },
     Encode {
+        #[clap(arg_enum, value_parser, required = true)]
+        /// image formats, each printed on its own line
+        formats: Vec<ImageFormat>,
+
+        #[clap(short, long, value_parser)]
+        /// Print only the base64 strings, without the "base64 encoded" prefix
+        quiet: bool,
+    },
+    /// Time generating and png encoding identicons of synthetic names, without writing files
+    Bench {
+        #[clap(short, long, default_value_t = DEFAULT_BENCH_COUNT, value_parser, value_name = "U32")]
+        /// The number of identicons to generate
+        count: u32,
+
+        #[clap(short, long, value_parser = clap::value_parser!(u32).range(..i64::from(MAX_PIXELS)), value_name = "U32")]
+        /// The number of pixels of each square; the top-level --size if absent
+        size: Option<u32>,
+    },
+    #[clap(hide = true)]
+    /// Print a completion script for the given shell
+    Completions {
         #[clap(arg_enum, value_parser)]
-        /// image format
-        format: ImageFormat,
+        shell: Shell,
     },
 }
 
 #[derive(Error, Debug)]
 enum Error {
-    #[error("invalid color=[{val}], expect format=[<u8>,<u8>,<u8>]")]
-    InvalidColor { val: String },
+    #[error(transparent)]
+    InvalidColor(#[from] color::Error),
+    #[error("invalid border=[{val}], expect format=[<u32>:<u8>,<u8>,<u8>] with a width less than 613566757")]
+    InvalidBorder { val: String },
+    #[error("missing name, expect [STRING]... before the subcommand")]
+    MissingName,
+    #[error("invalid name=[{name}] for a file name, expect no path separators")]
+    InvalidFileName { name: String },
+    #[error("unsupported file extension of path=[{path}], expect one of png, jpeg, jpg, gif")]
+    UnsupportedExtension { path: String },
     #[error(transparent)]
     InvalidIdenticon(#[from] identicon::Error),
 }
 
 type Result<T> = result::Result<T, Error>;
 
+// what a subcommand produced for one format
+enum Output {
+    File(PathBuf),
+    Base64(String),
+}
+
 fn parse_color(s: &str) -> Result<color::RGB> {
-    let mut iter = s.split(BACKGROUND_DELIMITER);
-    let r = iter
-        .next()
-        .and_then(|s| s.parse::<u8>().ok())
-        .ok_or(Error::InvalidColor { val: s.to_string() })?;
-    let g = iter
-        .next()
-        .and_then(|s| s.parse::<u8>().ok())
-        .ok_or(Error::InvalidColor { val: s.to_string() })?;
-    let b = iter
-        .next()
-        .and_then(|s| s.parse::<u8>().ok())
-        .ok_or(Error::InvalidColor { val: s.to_string() })?;
-    Ok(color::RGB::new(r, g, b))
-}
-
-fn main() -> Result<()> {
+    Ok(color::parse(s)?)
+}
+
+fn parse_background(s: &str) -> Result<Background> {
+    match s {
+        TRANSPARENT_BACKGROUND => Ok(Background::Transparent),
+        _ => Ok(Background::Color(parse_color(s)?)),
+    }
+}
+
+fn parse_border(s: &str) -> Result<(u32, color::RGB)> {
+    let (width, color) = s
+        .split_once(BORDER_DELIMITER)
+        .ok_or(Error::InvalidBorder { val: s.to_string() })?;
+    let width = width
+        .parse::<u32>()
+        .ok()
+        .filter(|width| *width < MAX_PIXELS)
+        .ok_or(Error::InvalidBorder { val: s.to_string() })?;
+    Ok((width, parse_color(color)?))
+}
+
+// the output format implied by the path's extension
+fn parse_extension(path: &Path) -> Result<OutputFormat> {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(|ext| ext.parse::<OutputFormat>().ok())
+        .ok_or(Error::UnsupportedExtension {
+            path: path.display().to_string(),
+        })
+}
+
+// a completion script offering the subcommands and long options; clap_complete
+// isn't a dependency, so this covers just the words, not their values
+fn completions(shell: Shell) -> String {
+    let cli = Cli::command();
+    let bin = env!("CARGO_BIN_NAME");
+    let subcommands: Vec<&str> = cli
+        .get_subcommands()
+        .filter(|cmd| !cmd.is_hide_set())
+        .map(|cmd| cmd.get_name())
+        .collect();
+    let options: Vec<String> = cli
+        .get_arguments()
+        .filter_map(|arg| arg.get_long())
+        .map(|long| format!("--{}", long))
+        .collect();
+    let words = [subcommands.join(" "), options.join(" ")].join(" ");
+    match shell {
+        Shell::Bash => format!(
+            "_{bin}() {{\n    COMPREPLY=($(compgen -W \"{words}\" -- \"${{COMP_WORDS[COMP_CWORD]}}\"))\n}}\ncomplete -F _{bin} {bin}\n",
+            bin = bin,
+            words = words
+        ),
+        Shell::Zsh => format!(
+            "#compdef {bin}\n_arguments '*: :({words})'\n",
+            bin = bin,
+            words = words
+        ),
+        Shell::Fish => subcommands
+            .iter()
+            .map(|cmd| format!("complete -c {} -f -a {}\n", bin, cmd))
+            .chain(options.iter().map(|opt| {
+                format!("complete -c {} -l {}\n", bin, opt.trim_start_matches('-'))
+            }))
+            .collect(),
+    }
+}
+
+// generate count identicons of size from synthetic names and print the rate
+fn bench(count: u32, size: u32) -> Result<()> {
+    let start = Instant::now();
+    for i in 0..count {
+        let name = format!("bench-{}", i);
+        let identicon = identicon::Identicon::new(&name, size, identicon::DEFAULT_BACKGROUND)?;
+        identicon.render_to_vec(OutputFormat::Png)?;
+    }
+    let secs = start.elapsed().as_secs_f64();
+    println!(
+        "{} identicons in {:.3}s: {:.1} images/sec",
+        count,
+        secs,
+        f64::from(count) / secs.max(f64::EPSILON)
+    );
+    Ok(())
+}
+
+fn run() -> Result<()> {
     let cli = Cli::parse();
-    let identicon = identicon::Identicon::new(&cli.name, cli.size, cli.background)?;
-    match &cli.command {
-        Command::Render { path } => Ok(identicon.render(&path)?),
-        Command::Encode {
-            format: ImageFormat::PNG,
-        } => Ok(println!("base64 encoded: {}", identicon.png()?)),
-        Command::Encode {
-            format: ImageFormat::JPEG,
-        } => Ok(println!("base64 encoded: {}", identicon.jpeg()?)),
-        Command::Encode {
-            format: ImageFormat::GIF,
-        } => Ok(println!("base64 encoded: {}", identicon.gif()?)),
+    if let Command::Completions { shell } = cli.command {
+        print!("{}", completions(shell));
+        return Ok(());
+    }
+    if let Command::Bench { count, size } = cli.command {
+        return bench(count, size.unwrap_or(cli.size));
+    }
+    if cli.names.is_empty() {
+        return Err(Error::MissingName);
+    }
+    for name in &cli.names {
+        generate(&cli, name)?;
+    }
+    Ok(())
+}
+
+// render or encode the identicon of name as the subcommand says; with several
+// names, render writes into the path as a directory and outputs are labeled
+fn generate(cli: &Cli, name: &str) -> Result<()> {
+    let multiple = cli.names.len() > 1;
+    let background = match &cli.background {
+        Background::Color(color) => color.clone(),
+        Background::Transparent => identicon::DEFAULT_BACKGROUND,
+    };
+    let mut identicon = match &cli.seed {
+        Some(seed) => identicon::Identicon::new_salted(name, seed, cli.size, background)?,
+        None => identicon::Identicon::new(name, cli.size, background)?,
+    };
+    identicon.set_transparent(matches!(cli.background, Background::Transparent));
+    if let Some(foreground) = &cli.foreground {
+        identicon.set_foreground(foreground.clone());
+    }
+    identicon.set_padding(cli.padding)?;
+    if let Some((width, color)) = &cli.border {
+        identicon.set_border(*width, color.clone())?;
+    }
+    let outputs = match &cli.command {
+        Command::Render { path, formats } if multiple => {
+            if name.contains(std::path::is_separator) {
+                return Err(Error::InvalidFileName {
+                    name: name.to_string(),
+                });
+            }
+            // reported like the library's own directory creation
+            fs::create_dir_all(path).map_err(|source| identicon::Error::Io {
+                path: path.display().to_string(),
+                source,
+            })?;
+            let formats: Vec<OutputFormat> = match formats.is_empty() {
+                true => vec![OutputFormat::Png],
+                false => formats.iter().map(|f| OutputFormat::from(*f)).collect(),
+            };
+            let mut outputs = Vec::new();
+            for format in formats {
+                let path = path.join(format!("{}.{}", name, format.extension()));
+                identicon.render_as(&path, format)?;
+                outputs.push((format, Output::File(path)));
+            }
+            outputs
+        }
+        Command::Render { path, formats } if formats.is_empty() => {
+            let format = parse_extension(path)?;
+            identicon.render(path)?;
+            vec![(format, Output::File(path.clone()))]
+        }
+        Command::Render { path, formats } => {
+            let mut outputs = Vec::new();
+            for format in formats.iter().map(|f| OutputFormat::from(*f)) {
+                let path = path.with_extension(format.extension());
+                identicon.render_as(&path, format)?;
+                outputs.push((format, Output::File(path)));
+            }
+            outputs
+        }
+        Command::Encode { formats, .. } => {
+            let mut outputs = Vec::new();
+            for format in formats.iter().map(|f| OutputFormat::from(*f)) {
+                outputs.push((format, Output::Base64(identicon.encode(format)?)));
+            }
+            outputs
+        }
+        Command::Bench { .. } | Command::Completions { .. } => {
+            unreachable!("handled before building the identicon")
+        }
+    };
+
+    if cli.json {
+        println!("{}", json(name, &identicon, &outputs));
+        return Ok(());
+    }
+    let quiet = matches!(cli.command, Command::Encode { quiet: true, .. });
+    for (format, output) in &outputs {
+        let label: Vec<&str> = [
+            multiple.then_some(name),
+            (outputs.len() > 1).then_some(format.extension()),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        match output {
+            Output::File(_) => {}
+            Output::Base64(encoded) if quiet => println!("{}", encoded),
+            Output::Base64(encoded) if label.is_empty() => {
+                println!("base64 encoded: {}", encoded)
+            }
+            Output::Base64(encoded) => {
+                println!("base64 encoded {}: {}", label.join(" "), encoded)
+            }
+        }
+    }
+    Ok(())
+}
+
+// the identicon's dimensions and colors with the outputs, hand-rolled since
+// there is no serde dependency:
+// {"name":"...","width":u32,"height":u32,"foreground":[r,g,b],"background":[r,g,b],
+//  "outputs":[{"format":"png","path":"..."} or {"format":"png","base64":"..."}]}
+fn json(
+    name: &str,
+    identicon: &identicon::Identicon,
+    outputs: &[(OutputFormat, Output)],
+) -> String {
+    let rgb = |color: &color::RGB| {
+        let [r, g, b] = color.as_pixel().0;
+        format!("[{},{},{}]", r, g, b)
+    };
+    let outputs: Vec<String> = outputs
+        .iter()
+        .map(|(format, output)| {
+            let (key, val) = match output {
+                Output::File(path) => ("path", path.display().to_string()),
+                Output::Base64(encoded) => ("base64", encoded.clone()),
+            };
+            format!(
+                "{{\"format\":\"{}\",\"{}\":{}}}",
+                format.extension(),
+                key,
+                json_string(&val)
+            )
+        })
+        .collect();
+    let (width, height) = identicon.dimensions();
+    format!(
+        "{{\"name\":{},\"width\":{},\"height\":{},\"foreground\":{},\"background\":{},\"outputs\":[{}]}}",
+        json_string(name),
+        width,
+        height,
+        rgb(identicon.foreground()),
+        rgb(identicon.background()),
+        outputs.join(",")
+    )
+}
+
+// s as a quoted json string
+fn json_string(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", u32::from(c))),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("error: {}", err);
+        process::exit(1);
     }
 }
@@ -0,0 +1,113 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::{color, Identicon, OutputFormat, Result};
+
+type Key = (String, u32, OutputFormat);
+
+// least-recently-used cache of encoded identicons sharing one background
+pub struct IdenticonCache {
+    capacity: usize,
+    background: color::RGB,
+    entries: HashMap<Key, Vec<u8>>,
+    // keys from least to most recently used
+    recency: VecDeque<Key>,
+    hits: usize,
+    misses: usize,
+}
+
+impl IdenticonCache {
+    // capacity: the maximum number of encoded images kept, at least one
+    pub fn new(capacity: usize, background: color::RGB) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            background,
+            entries: HashMap::new(),
+            recency: VecDeque::new(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    // the encoded image, rendered only if it isn't cached yet
+    pub fn get_or_render(&mut self, name: &str, size: u32, format: OutputFormat) -> Result<&[u8]> {
+        let key = (name.to_string(), size, format);
+        if self.entries.contains_key(&key) {
+            self.hits += 1;
+            if let Some(pos) = self.recency.iter().position(|k| *k == key) {
+                self.recency.remove(pos);
+            }
+        } else {
+            self.misses += 1;
+            let identicon = Identicon::new(name, size, self.background.clone())?;
+            let (bytes, _) = identicon.render_to_vec(format)?;
+            if self.entries.len() >= self.capacity {
+                if let Some(oldest) = self.recency.pop_front() {
+                    self.entries.remove(&oldest);
+                }
+            }
+            self.entries.insert(key.clone(), bytes);
+        }
+        self.recency.push_back(key.clone());
+        Ok(&self.entries[&key])
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // number of lookups served from the cache
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    // number of lookups that had to render
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IdenticonCache;
+    use crate::{OutputFormat, DEFAULT_BACKGROUND};
+
+    #[test]
+    fn hit_returns_same_bytes() {
+        let mut cache = IdenticonCache::new(2, DEFAULT_BACKGROUND);
+        let first = cache
+            .get_or_render("21012146", 10, OutputFormat::Png)
+            .unwrap()
+            .to_vec();
+        let second = cache
+            .get_or_render("21012146", 10, OutputFormat::Png)
+            .unwrap()
+            .to_vec();
+        assert_eq!(first, second);
+        assert_eq!((cache.hits(), cache.misses()), (1, 1));
+
+        cache
+            .get_or_render("21012146", 20, OutputFormat::Png)
+            .unwrap();
+        assert_eq!((cache.hits(), cache.misses()), (1, 2));
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = IdenticonCache::new(2, DEFAULT_BACKGROUND);
+        cache.get_or_render("a", 10, OutputFormat::Png).unwrap();
+        cache.get_or_render("b", 10, OutputFormat::Png).unwrap();
+        cache.get_or_render("a", 10, OutputFormat::Png).unwrap();
+        cache.get_or_render("c", 10, OutputFormat::Png).unwrap();
+        assert_eq!(cache.len(), 2);
+
+        let misses = cache.misses();
+        cache.get_or_render("a", 10, OutputFormat::Png).unwrap();
+        assert_eq!(cache.misses(), misses);
+        cache.get_or_render("b", 10, OutputFormat::Png).unwrap();
+        assert_eq!(cache.misses(), misses + 1);
+    }
+}
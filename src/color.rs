@@ -8,6 +8,7 @@ pub const HUE_MAX: u16 = 360;
 pub const SAT_MAX: u16 = 100;
 pub const LUM_MAX: u16 = 100;
 const RGB_MAX: u16 = 255;
+const CHANNEL_DELIMITER: char = ',';
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct RGB(Rgb<u8>);
@@ -18,7 +19,20 @@ pub struct HSL {
     lum: f32, // range: [0, 100]
 }
 
-#[derive(Error, Debug)]
+// a fixed set of approved colors
+#[derive(Debug, PartialEq, Clone)]
+pub struct Palette(pub Vec<RGB>);
+
+// how fractional channel values are turned into integers
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Rounding {
+    #[default]
+    Round,
+    Floor,
+    Ceil,
+}
+
+#[derive(Error, Debug, PartialEq)]
 pub enum Error {
     #[error("expect {name} between 0.0 and {max} but found {val}")]
     HSLOutOfBounds {
@@ -26,10 +40,67 @@ pub enum Error {
         val: f32,
         max: f32,
     },
+    #[error(
+        "invalid color=[{val}], expect <u8>,<u8>,<u8>, #rgb, #rrggbb or a css basic color name"
+    )]
+    InvalidColor { val: String },
 }
 
 pub type Result<T> = result::Result<T, Error>;
 
+// the sixteen css basic colors, plus the aliases aqua, fuchsia and grey
+const NAMED: [(&str, RGB); 19] = [
+    ("black", RGB::new(0, 0, 0)),
+    ("silver", RGB::new(192, 192, 192)),
+    ("gray", RGB::new(128, 128, 128)),
+    ("grey", RGB::new(128, 128, 128)),
+    ("white", RGB::new(255, 255, 255)),
+    ("maroon", RGB::new(128, 0, 0)),
+    ("red", RGB::new(255, 0, 0)),
+    ("purple", RGB::new(128, 0, 128)),
+    ("fuchsia", RGB::new(255, 0, 255)),
+    ("magenta", RGB::new(255, 0, 255)),
+    ("green", RGB::new(0, 128, 0)),
+    ("lime", RGB::new(0, 255, 0)),
+    ("olive", RGB::new(128, 128, 0)),
+    ("yellow", RGB::new(255, 255, 0)),
+    ("navy", RGB::new(0, 0, 128)),
+    ("blue", RGB::new(0, 0, 255)),
+    ("teal", RGB::new(0, 128, 128)),
+    ("aqua", RGB::new(0, 255, 255)),
+    ("cyan", RGB::new(0, 255, 255)),
+];
+
+// a color written as r,g,b with each channel in [0, 255], as css hex #rgb or
+// #rrggbb, or as a css basic color name; names and hex digits ignore case
+pub fn parse(s: &str) -> Result<RGB> {
+    let invalid = || Error::InvalidColor { val: s.to_string() };
+    if let Some(hex) = s.strip_prefix('#') {
+        let digits: Vec<u8> = hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<_>>()
+            .ok_or_else(invalid)?;
+        return match digits[..] {
+            [r, g, b] => Ok(RGB::new(r * 17, g * 17, b * 17)),
+            [r1, r0, g1, g0, b1, b0] => Ok(RGB::new(r1 << 4 | r0, g1 << 4 | g0, b1 << 4 | b0)),
+            _ => Err(invalid()),
+        };
+    }
+    if let Some((_, color)) = NAMED.iter().find(|(name, _)| name.eq_ignore_ascii_case(s)) {
+        return Ok(color.clone());
+    }
+    let channels: Vec<u8> = s
+        .split(CHANNEL_DELIMITER)
+        .map(|c| c.trim().parse::<u8>().ok())
+        .collect::<Option<_>>()
+        .ok_or_else(invalid)?;
+    match channels[..] {
+        [r, g, b] => Ok(RGB::new(r, g, b)),
+        _ => Err(invalid()),
+    }
+}
+
 impl RGB {
     pub const fn new(red: u8, green: u8, blue: u8) -> Self {
         Self(Rgb([red, green, blue]))
@@ -38,6 +109,133 @@ impl RGB {
     pub fn as_pixel(&self) -> Rgb<u8> {
         self.0
     }
+
+    // css hex notation, e.g. #ff0000
+    pub fn to_hex(&self) -> String {
+        let [r, g, b] = self.0 .0;
+        format!("#{:02x}{:02x}{:02x}", r, g, b)
+    }
+
+    // delta added to each channel, clamped to [0, 255]; positive brightens,
+    // negative darkens
+    pub fn saturating_add(&self, delta: i16) -> RGB {
+        let channel = |c: u8| (i32::from(c) + i32::from(delta)).clamp(0, i32::from(RGB_MAX)) as u8;
+        let [r, g, b] = self.0 .0;
+        RGB::new(channel(r), channel(g), channel(b))
+    }
+
+    // this color drawn with the given opacity in [0, 1] over base
+    pub fn blend(&self, base: &RGB, opacity: f32) -> RGB {
+        let opacity = opacity.clamp(0.0, 1.0);
+        let channel = |i: usize| {
+            let (fg, bg) = (f32::from(self.0 .0[i]), f32::from(base.0 .0[i]));
+            (fg * opacity + bg * (1.0 - opacity)).round() as u8
+        };
+        RGB::new(channel(0), channel(1), channel(2))
+    }
+
+    // WCAG relative luminance in [0, 1]
+    pub fn relative_luminance(&self) -> f32 {
+        let linear = |c: u8| linearize(f32::from(c) / f32::from(RGB_MAX));
+        let [r, g, b] = self.0 .0;
+        0.2126 * linear(r) + 0.7152 * linear(g) + 0.0722 * linear(b)
+    }
+
+    // WCAG contrast ratio in [1, 21]; symmetric in its arguments
+    pub fn contrast_ratio(&self, other: &RGB) -> f32 {
+        let (l1, l2) = (self.relative_luminance(), other.relative_luminance());
+        (l1.max(l2) + 0.05) / (l1.min(l2) + 0.05)
+    }
+
+    // euclidean distance between the channel values, up to sqrt(3) * 255
+    pub fn distance_rgb(&self, other: &RGB) -> f32 {
+        let [r, g, b] = [0, 1, 2].map(|i| f32::from(self.0 .0[i]) - f32::from(other.0 .0[i]));
+        (r * r + g * g + b * b).sqrt()
+    }
+
+    // CIE76 distance, euclidean in CIELAB; about 2.3 is just noticeable
+    pub fn distance_lab(&self, other: &RGB) -> f32 {
+        let (a, b) = (self.as_lab(), other.as_lab());
+        let [dl, da, db] = [0, 1, 2].map(|i| a[i] - b[i]);
+        (dl * dl + da * da + db * db).sqrt()
+    }
+
+    // [L*, a*, b*] under the D65 white point, by way of CIEXYZ
+    fn as_lab(&self) -> [f32; 3] {
+        // reference white of D65
+        const WHITE: [f32; 3] = [0.950_47, 1.0, 1.088_83];
+        const EPSILON: f32 = 216.0 / 24389.0;
+        const KAPPA: f32 = 24389.0 / 27.0;
+        let [r, g, b] = self
+            .0
+             .0
+            .map(|c| linearize(f32::from(c) / f32::from(RGB_MAX)));
+        let xyz = [
+            0.412_456_4 * r + 0.357_576_1 * g + 0.180_437_5 * b,
+            0.212_672_9 * r + 0.715_152_2 * g + 0.072_175 * b,
+            0.019_333_9 * r + 0.119_192 * g + 0.950_304_1 * b,
+        ];
+        let [fx, fy, fz] = [0, 1, 2].map(|i| {
+            let t = xyz[i] / WHITE[i];
+            if t > EPSILON {
+                t.cbrt()
+            } else {
+                (KAPPA * t + 16.0) / 116.0
+            }
+        });
+        [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+    }
+
+    pub fn as_hsl(&self) -> HSL {
+        let [r, g, b] = self.0 .0.map(|c| f32::from(c) / f32::from(RGB_MAX));
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let lum = (max + min) / 2.0;
+        let delta = max - min;
+        if delta == 0.0 {
+            return HSL {
+                hue: 0.0,
+                sat: 0.0,
+                lum: lum * f32::from(LUM_MAX),
+            };
+        }
+
+        let sat = delta / (1.0 - (2.0 * lum - 1.0).abs());
+        let hue = if max == r {
+            ((g - b) / delta).rem_euclid(6.0)
+        } else if max == g {
+            (b - r) / delta + 2.0
+        } else {
+            (r - g) / delta + 4.0
+        };
+        HSL {
+            hue: hue * 60.0,
+            sat: (sat * f32::from(SAT_MAX)).min(f32::from(SAT_MAX)),
+            lum: lum * f32::from(LUM_MAX),
+        }
+    }
+}
+
+impl Palette {
+    // the entry whose hue is closest to hue around the color wheel; None if
+    // the palette is empty
+    pub fn nearest_hue(&self, hue: f32) -> Option<&RGB> {
+        let distance = |color: &RGB| {
+            let d = (color.as_hsl().hue() - hue).rem_euclid(f32::from(HUE_MAX));
+            d.min(f32::from(HUE_MAX) - d)
+        };
+        self.0
+            .iter()
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+    }
+}
+
+// the crate's default background gray, crate::DEFAULT_BACKGROUND, so that a
+// defaulted color is a usable background
+impl Default for RGB {
+    fn default() -> Self {
+        crate::DEFAULT_BACKGROUND
+    }
 }
 
 impl fmt::Display for RGB {
@@ -47,35 +245,77 @@ impl fmt::Display for RGB {
 }
 
 impl HSL {
+    pub fn hue(&self) -> f32 {
+        self.hue
+    }
+
+    pub fn sat(&self) -> f32 {
+        self.sat
+    }
+
+    pub fn lum(&self) -> f32 {
+        self.lum
+    }
+
+    // the same color with luminance lowered by amount, stopping at black
+    pub fn darken(&self, amount: f32) -> HSL {
+        HSL {
+            hue: self.hue,
+            sat: self.sat,
+            lum: (self.lum - amount).clamp(0.0, f32::from(LUM_MAX)),
+        }
+    }
+
+    // NaN fails the range checks, like any value outside them
     pub fn new(hue: f32, sat: f32, lum: f32) -> Result<Self> {
-        if hue < 0.0 || hue > f32::from(HUE_MAX) {
+        if !(0.0..=f32::from(HUE_MAX)).contains(&hue) {
             Err(Error::HSLOutOfBounds {
                 name: "hue",
                 val: hue,
                 max: f32::from(HUE_MAX),
             })
-        } else if sat < 0.0 || sat > f32::from(SAT_MAX) {
+        } else if !(0.0..=f32::from(SAT_MAX)).contains(&sat) {
             Err(Error::HSLOutOfBounds {
                 name: "sat",
                 val: sat,
                 max: f32::from(SAT_MAX),
             })
-        } else if lum < 0.0 || lum > f32::from(LUM_MAX) {
+        } else if !(0.0..=f32::from(LUM_MAX)).contains(&lum) {
             Err(Error::HSLOutOfBounds {
                 name: "lum",
                 val: lum,
                 max: f32::from(LUM_MAX),
             })
         } else {
-            Ok(Self {
-                hue: hue,
-                sat: sat,
-                lum: lum,
-            })
+            Ok(Self { hue, sat, lum })
         }
     }
 
     pub fn as_rgb(&self) -> RGB {
+        self.as_rgb_with(Rounding::default())
+    }
+
+    // as_rgb with a choice of rounding, e.g. to match a reference palette
+    pub fn as_rgb_with(&self, rounding: Rounding) -> RGB {
+        let [r, g, b] = self.channels();
+        let channel = |c: f32| {
+            let c = c * f32::from(RGB_MAX);
+            match rounding {
+                Rounding::Round => c.round() as u8,
+                Rounding::Floor => c.floor() as u8,
+                Rounding::Ceil => c.ceil() as u8,
+            }
+        };
+        RGB::new(channel(r), channel(g), channel(b))
+    }
+
+    // the gamma-expanded rgb channels in [0, 1], for blending in linear space
+    pub fn as_rgb_linear(&self) -> [f32; 3] {
+        self.channels().map(linearize)
+    }
+
+    // the srgb channels in [0, 1] before rounding
+    fn channels(&self) -> [f32; 3] {
         let hue = self.hue / f32::from(HUE_MAX);
         let sat = self.sat / f32::from(SAT_MAX);
         let lum = self.lum / f32::from(LUM_MAX);
@@ -86,12 +326,7 @@ impl HSL {
         let r = Self::compute_rgb(c, m, hue + 1.0 / 3.0);
         let g = Self::compute_rgb(c, m, hue);
         let b = Self::compute_rgb(c, m, hue - 1.0 / 3.0);
-
-        RGB::new(
-            (r * f32::from(RGB_MAX)).round() as u8,
-            (g * f32::from(RGB_MAX)).round() as u8,
-            (b * f32::from(RGB_MAX)).round() as u8,
-        )
+        [r, g, b]
     }
 
     fn compute_rgb(c: f32, m: f32, h: f32) -> f32 {
@@ -115,9 +350,18 @@ impl HSL {
     }
 }
 
+// the srgb transfer function, from a gamma-encoded channel in [0, 1] to linear
+fn linearize(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{HSL, RGB};
+    use super::{parse, Error, Palette, Rounding, HSL, RGB};
 
     #[test]
     fn to_black() {
@@ -181,4 +425,161 @@ mod tests {
         let rgb: RGB = HSL::new(343.4, 55.3, 88.9).unwrap().as_rgb();
         assert_eq!(color, rgb);
     }
+
+    #[test]
+    fn out_of_bounds_error() {
+        let err = HSL::new(0.0, 120.0, 50.0).err().unwrap();
+        assert_eq!(
+            err,
+            Error::HSLOutOfBounds {
+                name: "sat",
+                val: 120.0,
+                max: 100.0,
+            }
+        );
+        assert_ne!(err, HSL::new(400.0, 0.0, 0.0).err().unwrap());
+        assert!(HSL::new(f32::NAN, 0.0, 0.0).is_err());
+        assert!(HSL::new(0.0, f32::NAN, 0.0).is_err());
+        assert!(HSL::new(0.0, 0.0, f32::NAN).is_err());
+    }
+
+    #[test]
+    fn blend() {
+        let fg = RGB::new(200, 100, 0);
+        let bg = RGB::new(100, 0, 255);
+        assert_eq!(fg.blend(&bg, 1.0), fg);
+        assert_eq!(fg.blend(&bg, 0.0), bg);
+        assert_eq!(fg.blend(&bg, 0.5), RGB::new(150, 50, 128));
+    }
+
+    #[test]
+    fn contrast() {
+        let black = RGB::new(0, 0, 0);
+        let white = RGB::new(255, 255, 255);
+        assert_eq!(black.relative_luminance(), 0.0);
+        assert_eq!(white.relative_luminance(), 1.0);
+        assert!((black.contrast_ratio(&white) - 21.0).abs() < 1e-4);
+        assert_eq!(black.contrast_ratio(&white), white.contrast_ratio(&black));
+        assert_eq!(white.contrast_ratio(&white), 1.0);
+    }
+
+    #[test]
+    fn linear() {
+        assert_eq!(HSL::new(0.0, 0.0, 100.0).unwrap().as_rgb_linear(), [1.0; 3]);
+        assert_eq!(HSL::new(0.0, 0.0, 0.0).unwrap().as_rgb_linear(), [0.0; 3]);
+        for c in HSL::new(0.0, 0.0, 50.0).unwrap().as_rgb_linear() {
+            assert!((c - 0.214_041).abs() < 1e-5, "{}", c);
+        }
+    }
+
+    #[test]
+    fn saturating_add() {
+        let color = RGB::new(10, 128, 250);
+        assert_eq!(color.saturating_add(20), RGB::new(30, 148, 255));
+        assert_eq!(color.saturating_add(-20), RGB::new(0, 108, 230));
+        assert_eq!(color.saturating_add(300), RGB::new(255, 255, 255));
+        assert_eq!(color.saturating_add(-300), RGB::new(0, 0, 0));
+        assert_eq!(color.saturating_add(i16::MAX), RGB::new(255, 255, 255));
+    }
+
+    #[test]
+    fn palette_nearest_hue() {
+        let red = RGB::new(255, 0, 0);
+        let green = RGB::new(0, 255, 0);
+        let blue = RGB::new(0, 0, 255);
+        let palette = Palette(vec![red.clone(), green.clone(), blue.clone()]);
+        assert_eq!(palette.nearest_hue(100.0), Some(&green));
+        assert_eq!(palette.nearest_hue(350.0), Some(&red));
+        assert_eq!(palette.nearest_hue(200.0), Some(&blue));
+        assert_eq!(Palette(Vec::new()).nearest_hue(0.0), None);
+    }
+
+    #[test]
+    fn distance() {
+        let (black, white) = (RGB::new(0, 0, 0), RGB::new(255, 255, 255));
+        let expected = 3f32.sqrt() * 255.0;
+        assert!((black.distance_rgb(&white) - expected).abs() < 1e-3);
+        assert_eq!(white.distance_rgb(&black), black.distance_rgb(&white));
+        let red = RGB::new(200, 30, 40);
+        assert_eq!(red.distance_rgb(&red), 0.0);
+        assert_eq!(red.distance_lab(&red), 0.0);
+        // black and white span the whole lightness axis
+        assert!((black.distance_lab(&white) - 100.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn parse_channels() {
+        assert_eq!(parse("255,0,0"), Ok(RGB::new(255, 0, 0)));
+        assert_eq!(parse("1, 171, 16"), Ok(RGB::new(1, 171, 16)));
+        for bad in ["", "1,2", "1,2,3,4", "256,0,0", "a,b,c"] {
+            assert!(
+                matches!(parse(bad), Err(Error::InvalidColor { .. })),
+                "{}",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn parse_hex() {
+        assert_eq!(parse("#01ab10"), Ok(RGB::new(1, 171, 16)));
+        assert_eq!(parse("#01AB10"), Ok(RGB::new(1, 171, 16)));
+        assert_eq!(parse("#f0a"), Ok(RGB::new(255, 0, 170)));
+        for bad in ["#", "#12", "#12345", "#gggggg", "#01ab10ff"] {
+            assert!(parse(bad).is_err(), "{}", bad);
+        }
+        let color = RGB::new(12, 200, 99);
+        assert_eq!(parse(&color.to_hex()), Ok(color));
+    }
+
+    #[test]
+    fn parse_named() {
+        assert_eq!(parse("red"), Ok(RGB::new(255, 0, 0)));
+        assert_eq!(parse("Navy"), Ok(RGB::new(0, 0, 128)));
+        assert_eq!(parse("grey"), parse("gray"));
+        assert!(parse("rebeccapurple").is_err());
+    }
+
+    #[test]
+    fn default() {
+        assert_eq!(RGB::default(), RGB::new(240, 240, 240));
+        assert_eq!(RGB::default(), crate::DEFAULT_BACKGROUND);
+    }
+
+    #[test]
+    fn hex() {
+        assert_eq!(RGB::new(255, 0, 0).to_hex(), "#ff0000");
+        assert_eq!(RGB::new(1, 171, 16).to_hex(), "#01ab10");
+    }
+
+    #[test]
+    fn from_rgb() {
+        for (h, s, l) in [(55.2, 70.0, 30.0), (201.3, 40.2, 20.9), (343.4, 55.3, 88.9)] {
+            let rgb = HSL::new(h, s, l).unwrap().as_rgb();
+            let hsl = rgb.as_hsl();
+            assert!((hsl.hue() - h).abs() < 1.0, "{} {}", hsl.hue(), h);
+            assert!((hsl.sat() - s).abs() < 1.0, "{} {}", hsl.sat(), s);
+            assert!((hsl.lum() - l).abs() < 1.0, "{} {}", hsl.lum(), l);
+            assert_eq!(hsl.as_rgb(), rgb);
+        }
+    }
+
+    #[test]
+    fn darken() {
+        let hsl = HSL::new(120.0, 50.0, 40.0).unwrap();
+        assert_eq!(hsl.darken(15.0).lum(), 25.0);
+        assert_eq!(hsl.darken(15.0).hue(), 120.0);
+        assert_eq!(hsl.darken(60.0).lum(), 0.0);
+        assert_eq!(hsl.darken(-70.0).lum(), 100.0);
+    }
+
+    #[test]
+    fn rounding() {
+        // 50% gray is 127.5 in every channel
+        let gray = HSL::new(0.0, 0.0, 50.0).unwrap();
+        assert_eq!(gray.as_rgb(), RGB::new(128, 128, 128));
+        assert_eq!(gray.as_rgb_with(Rounding::Round), RGB::new(128, 128, 128));
+        assert_eq!(gray.as_rgb_with(Rounding::Floor), RGB::new(127, 127, 127));
+        assert_eq!(gray.as_rgb_with(Rounding::Ceil), RGB::new(128, 128, 128));
+    }
 }
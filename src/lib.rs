@@ -1,156 +1,3981 @@
-use std::io::Cursor;
+use std::fmt;
+use std::fs;
+use std::io::{self, Cursor, Read, Write};
+use std::iter;
+use std::ops::Range;
 use std::path::Path;
 use std::result;
+use std::str::FromStr;
+use std::sync::OnceLock;
 
-use image::{ImageError, ImageOutputFormat, RgbImage};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::codecs::ico::{IcoEncoder, IcoFrame};
+use image::{
+    imageops, ColorType, Delay, DynamicImage, Frame, GrayImage, ImageError, ImageOutputFormat,
+    Luma, Rgb, RgbImage, Rgba, RgbaImage,
+};
 use md5::{Digest, Md5};
 use thiserror::Error;
 
+pub mod base64;
+#[cfg(feature = "cache")]
+pub mod cache;
 pub mod color;
-mod base64;
+pub mod prelude;
+mod rng;
+pub mod util;
+
+pub use image::imageops::FilterType;
+
+pub const DEFAULT_BACKGROUND: color::RGB = color::RGB::new(240, 240, 240);
 
 const SAT_MIN: u16 = 45;
 const SAT_MAX: u16 = 65;
 const LUM_MIN: u16 = 55;
 const LUM_MAX: u16 = 75;
+// the luminance range used instead on dark backgrounds, see light_on_dark
+const DARK_BG_LUM_MIN: u16 = 78;
+const DARK_BG_LUM_MAX: u16 = 90;
+// saturation and luminance of a background derived from the foreground hue
+const DERIVED_BG_SAT: f32 = 30.0;
+const DERIVED_BG_LUM: f32 = 92.0;
+// relative luminance below which a background contrasts more with white than
+// with black
+const DARK_LUMINANCE: f32 = 0.179;
 const NUM_SQUARES: u8 = 7;
 const JPEG_QUALITY: u8 = 255;
+// luminance drop of the darker squares in retro style
+const RETRO_DARKEN: f32 = 12.0;
+// extra hue separation when dispersing colors, absorbing the error of
+// rounding the nudged hues to rgb
+const HUE_SLACK: f32 = 1.0;
+
+const GRID_SIZE: usize = NUM_SQUARES as usize;
+// cells left of and including the center column, excluding the outer margin
+const NUM_PAINTS: usize = (NUM_SQUARES as usize - 2) * (NUM_SQUARES as usize / 2);
+// each paint cell is driven by one nibble of the hash and packed in one bit
+// of the u16 of paints_to_u16
+const _: () = assert!(NUM_SQUARES % 2 == 1 && NUM_PAINTS <= 16);
+
+// version byte leading the blob of to_bytes, and the blob's length
+const BLOB_VERSION: u8 = 1;
+const BLOB_LEN: usize = 34;
+// flags bits of the blob
+const BLOB_MIRROR: u8 = 1 << 0;
+const BLOB_RETRO: u8 = 1 << 1;
+const BLOB_TRANSPARENT: u8 = 1 << 2;
+const BLOB_GLYPH: u8 = 1 << 3;
+
+// set in the length prefix of a hashed domain
+const DOMAIN_TAG: u64 = 1 << 63;
+// the hash byte whose parity paints the whole center column in
+// center-weighted paints; neither the nibbles nor the foreground use it
+const CENTER_BYTE: usize = 8;
+// the hash byte choosing the center glyph
+const GLYPH_BYTE: usize = 9;
+// the hash byte choosing the style under Style::Auto
+const STYLE_BYTE: usize = 10;
+// half the width of a center glyph relative to its cell
+const GLYPH_EXTENT: f32 = 0.6;
+// the rehashes avoid_blank tries before settling for a blank identicon, as
+// a paint rule may paint nothing for any hash
+const MAX_REHASHES: usize = 64;
+// the constructors and resizing setters reject identicons whose dimensions
+// overflow u32, so every identicon drawn has dimensions that fit
+const FITS: &str = "dimensions fit in u32";
 
 type HashBytes = [u8; 16];
-type Paints = [bool; 15];
+type HueFn = Box<dyn Fn(&HashBytes) -> f32>;
+pub type Paints = [bool; NUM_PAINTS];
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Identicon {
+    hash: HashBytes,
     paints: Paints,
-    size: u32,
+    width: u32,
+    height: u32,
     foreground: color::RGB,
     background: color::RGB,
+    mirror: bool,
+    accent: Option<color::RGB>,
+    padding: u32,
+    border: Option<(u32, color::RGB)>,
+    jitter: u8,
+    opacity: f32,
+    shape: TileShape,
+    layout: Layout,
+    retro: bool,
+    transparent: bool,
+    shadow: Option<(i32, i32, color::RGB)>,
+    noise: u8,
+    glyph: bool,
+    ring_colors: Option<Vec<color::RGB>>,
+    rendered: Rendered,
+}
+
+// the memoized image of an identicon, left out of its debug output
+#[derive(Clone, Default)]
+struct Rendered(OnceLock<RgbImage>);
+
+impl fmt::Debug for Rendered {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Rendered")
+            .field(&self.0.get().is_some())
+            .finish()
+    }
+}
+
+// rows of rgb pixels in a byte buffer, each starting stride bytes after the
+// previous one; pixels outside width x rows are ignored
+struct PixelRows<'a> {
+    bytes: &'a mut [u8],
+    stride: usize,
+    width: u32,
+    rows: u32,
+}
+
+// configures an identicon before its colors and paints are derived
+pub struct IdenticonBuilder {
+    input: Vec<u8>,
+    size: u32,
+    background: color::RGB,
+    direction: Direction,
+    salt: Option<String>,
+    hash: Option<HashBytes>,
+    avoid_blank: bool,
+    paint_rule: Box<dyn Fn(u8) -> bool>,
+    algorithm: Algorithm,
+    light_on_dark: bool,
+    palette: Option<color::Palette>,
+    domain: Option<String>,
+    center_weighted: bool,
+    hue_fn: Option<HueFn>,
+    suppress_corners: bool,
+    style: Style,
+    derived_background: bool,
 }
 
+// how the hash turns into paints and a foreground; each version is frozen
+// once released so that existing identicons never change
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[non_exhaustive]
+pub enum Algorithm {
+    #[default]
+    V1,
+}
+
+// how a hash byte maps onto saturation and luminance; descending means
+// higher byte values give a less vivid color
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Direction {
+    Ascending,
+    #[default]
+    Descending,
+}
+
+// the shape each painted cell is drawn with
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum TileShape {
+    #[default]
+    Square,
+    // the ellipse inscribed in the cell
+    Circle,
+    // corners rounded with radius, clamped to half the cell size
+    RoundedSquare {
+        radius: u32,
+    },
+    // pointy-top hexagon inscribed in the cell
+    Hexagon,
+}
+
+// a preset of tile shape and layout; auto lets the hash choose one of the
+// others, so each name keeps its style
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+pub enum Style {
+    #[default]
+    Square,
+    Circle,
+    // squares with corners rounded by a quarter of the cell
+    Rounded,
+    // the hex layout
+    Hex,
+    Auto,
+}
+
+// how cells are arranged on the canvas
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Layout {
+    #[default]
+    Grid,
+    // odd rows shifted by half a cell and drawn as hexagons; jitter, tile
+    // shape and retro style are ignored
+    HexGrid,
+}
+
+// a simple shape drawn inside the center cell, see set_center_glyph
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Glyph {
+    // pointing up
+    Triangle,
+    Diamond,
+    Plus,
+    Ring,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum OutputFormat {
+    Png,
+    Jpeg,
+    Gif,
+}
+
+// unlike color::Error this can't derive PartialEq, since neither
+// image::ImageError nor png::EncodingError implement it
 #[derive(Error, Debug)]
 pub enum Error {
     #[error(transparent)]
     InvalidHSL(#[from] color::Error),
     #[error("encounter error saving image: {0}")]
     SaveImage(#[from] ImageError),
+    #[error("encounter error encoding image as {format}: {source}")]
+    Encode {
+        format: &'static str,
+        source: ImageError,
+    },
+    #[error("encounter error decoding image: {source}")]
+    Decode { source: ImageError },
+    #[error("encounter error writing png: {0}")]
+    WritePng(#[from] png::EncodingError),
+    #[error("foreground equals the transparent background")]
+    TransparentForeground,
+    #[error("{format} has no alpha channel for a transparent background")]
+    NoAlpha { format: &'static str },
+    #[error("encounter error accessing path=[{path}]: {source}")]
+    Io { path: String, source: io::Error },
+    #[error("invalid pixel buffer: {reason}")]
+    InvalidBuffer { reason: &'static str },
+    #[error("invalid identicon blob: {reason}")]
+    InvalidBlob { reason: &'static str },
+    #[error("unknown format=[{val}], expect one of png, jpeg, jpg, gif")]
+    UnknownFormat { val: String },
+    #[error("image too large, its width or height overflows u32")]
+    TooLarge,
 }
 
 pub type Result<T> = result::Result<T, Error>;
 
+impl PixelRows<'_> {
+    fn pixel_mut(&mut self, x: u32, y: u32) -> Option<&mut [u8]> {
+        if x >= self.width || y >= self.rows {
+            return None;
+        }
+        let start = y as usize * self.stride + x as usize * 3;
+        Some(&mut self.bytes[start..start + 3])
+    }
+
+    fn put(&mut self, x: u32, y: u32, pixel: Rgb<u8>) {
+        if let Some(bytes) = self.pixel_mut(x, y) {
+            bytes.copy_from_slice(&pixel.0);
+        }
+    }
+}
+
+impl TileShape {
+    // whether the pixel at (x, y) within a width x height cell is drawn,
+    // testing the pixel's center against the shape
+    fn covers(&self, x: u32, y: u32, width: u32, height: u32) -> bool {
+        let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+        let (w, h) = (width as f32, height as f32);
+        match self {
+            Self::Square => true,
+            Self::Circle => {
+                let (rx, ry) = (w / 2.0, h / 2.0);
+                ((px - rx) / rx).powi(2) + ((py - ry) / ry).powi(2) <= 1.0
+            }
+            Self::RoundedSquare { radius } => {
+                let r = (*radius).min(width / 2).min(height / 2) as f32;
+                let cx = px.clamp(r, w - r);
+                let cy = py.clamp(r, h - r);
+                (px - cx).powi(2) + (py - cy).powi(2) <= r * r
+            }
+            Self::Hexagon => {
+                let u = (px - w / 2.0).abs() / (w / 2.0);
+                let v = (py - h / 2.0).abs() / (h / 2.0);
+                u <= 1.0 && v <= 1.0 - u / 2.0
+            }
+        }
+    }
+}
+
+impl Style {
+    const CHOICES: [Style; 4] = [Self::Square, Self::Circle, Self::Rounded, Self::Hex];
+
+    // the style itself, or under auto the one chosen by the hash
+    fn resolve(self, hash: &HashBytes) -> Style {
+        match self {
+            Self::Auto => Self::CHOICES[usize::from(hash[STYLE_BYTE]) % Self::CHOICES.len()],
+            style => style,
+        }
+    }
+}
+
+impl Glyph {
+    const ALL: [Glyph; 4] = [Self::Triangle, Self::Diamond, Self::Plus, Self::Ring];
+
+    // whether the pixel at (x, y) within a width x height cell is drawn; the
+    // glyph is centered in the cell and spans GLYPH_EXTENT of it
+    fn covers(&self, x: u32, y: u32, width: u32, height: u32) -> bool {
+        // the pixel's center in [-1, 1] across the cell
+        let u = (x as f32 + 0.5) / width as f32 * 2.0 - 1.0;
+        let v = (y as f32 + 0.5) / height as f32 * 2.0 - 1.0;
+        let (s, au, av) = (GLYPH_EXTENT, u.abs(), v.abs());
+        match self {
+            Self::Triangle => av <= s && au <= (v + s) / 2.0,
+            Self::Diamond => au + av <= s,
+            Self::Plus => (au <= s / 3.0 && av <= s) || (av <= s / 3.0 && au <= s),
+            Self::Ring => (s * 0.55..=s).contains(&u.hypot(v)),
+        }
+    }
+}
+
+impl Error {
+    // whether the error comes from an invalid derived color
+    pub fn is_color(&self) -> bool {
+        matches!(self, Self::InvalidHSL(_))
+    }
+
+    // whether the error comes from encoding or saving the image
+    pub fn is_encoding(&self) -> bool {
+        matches!(
+            self,
+            Self::SaveImage(_)
+                | Self::Encode { .. }
+                | Self::WritePng(_)
+                | Self::TransparentForeground
+                | Self::NoAlpha { .. }
+        )
+    }
+}
+
+impl OutputFormat {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpeg",
+            Self::Gif => "gif",
+        }
+    }
+
+    // the usual file extension, without the leading dot
+    pub fn extension(&self) -> &'static str {
+        self.name()
+    }
+
+    fn mime_type(&self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+            Self::Gif => "image/gif",
+        }
+    }
+
+    fn image_format(&self) -> ImageOutputFormat {
+        match self {
+            Self::Png => ImageOutputFormat::Png,
+            Self::Jpeg => ImageOutputFormat::Jpeg(JPEG_QUALITY),
+            Self::Gif => ImageOutputFormat::Gif,
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "png" => Ok(Self::Png),
+            "jpeg" | "jpg" => Ok(Self::Jpeg),
+            "gif" => Ok(Self::Gif),
+            _ => Err(Error::UnknownFormat { val: s.to_string() }),
+        }
+    }
+}
+
+impl IdenticonBuilder {
+    // name: the input string to generate identicon
+    // size: the number of pixels of each square in the resulting image
+    // background: the background color
+    pub fn new(name: &str, size: u32, background: color::RGB) -> Self {
+        Self::from_bytes(name.as_bytes(), size, background)
+    }
+
+    // input: the raw bytes to hash in place of a name
+    pub fn from_bytes(input: &[u8], size: u32, background: color::RGB) -> Self {
+        Self {
+            input: input.to_vec(),
+            size,
+            background,
+            direction: Direction::default(),
+            salt: None,
+            hash: None,
+            avoid_blank: false,
+            paint_rule: Box::new(|nibble| nibble % 2 == 0),
+            algorithm: Algorithm::default(),
+            light_on_dark: false,
+            palette: None,
+            domain: None,
+            center_weighted: false,
+            hue_fn: None,
+            suppress_corners: false,
+            style: Style::default(),
+            derived_background: false,
+        }
+    }
+
+    // hash: used in place of the md5 of the input, bypassing hashing entirely;
+    // meant for deterministic tests
+    pub fn with_hash_bytes(mut self, hash: HashBytes) -> Self {
+        self.hash = Some(hash);
+        self
+    }
+
+    // salt: mixed into the hash so the same name differs across namespaces
+    pub fn salt(mut self, salt: &str) -> Self {
+        self.salt = Some(salt.to_string());
+        self
+    }
+
+    // domain: a fixed tag of the application hashed ahead of salt and name,
+    // e.g. "identicon-v1", keeping its hashes apart from other uses of md5
+    pub fn domain(mut self, domain: &str) -> Self {
+        self.domain = Some(domain.to_string());
+        self
+    }
+
+    // direction: how hash bytes map onto saturation and luminance
+    pub fn direction(mut self, direction: Direction) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    // avoid_blank: rehash a hash that paints no cells until one does, so that
+    // no input gets an empty identicon; a paint rule that stays blank after
+    // MAX_REHASHES rehashes keeps the blank identicon of the original hash
+    pub fn avoid_blank(mut self, avoid_blank: bool) -> Self {
+        self.avoid_blank = avoid_blank;
+        self
+    }
+
+    // rule: whether a cell is painted given its hash nibble in [0, 15];
+    // defaults to even nibbles, painting about half of the cells
+    pub fn paint_rule(mut self, rule: impl Fn(u8) -> bool + 'static) -> Self {
+        self.paint_rule = Box::new(rule);
+        self
+    }
+
+    // center_weighted: paint the whole center column or none of it by a
+    // dedicated hash byte, and the other cells less often the nearer they are
+    // to the edges; replaces the paint rule
+    pub fn center_weighted(mut self, center_weighted: bool) -> Self {
+        self.center_weighted = center_weighted;
+        self
+    }
+
+    // suppress_corners: never paint the corner cells of the pattern, nor their
+    // mirrors, for a softer outline
+    pub fn suppress_corners(mut self, suppress_corners: bool) -> Self {
+        self.suppress_corners = suppress_corners;
+        self
+    }
+
+    // hue_fn: the foreground hue in degrees given the hash, in place of the
+    // built-in mapping; wrapped into [0, 360), while a non-finite hue fails
+    // the build as out of bounds
+    pub fn hue_fn(mut self, hue_fn: impl Fn(&HashBytes) -> f32 + 'static) -> Self {
+        self.hue_fn = Some(Box::new(hue_fn));
+        self
+    }
+
+    // derived_background: replace the background with a pale tint of the
+    // foreground hue
+    pub fn derived_background(mut self, derived_background: bool) -> Self {
+        self.derived_background = derived_background;
+        self
+    }
+
+    // style: the tile shape and layout preset, possibly chosen by the hash;
+    // the shape and layout setters can still change it afterwards
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    // algorithm: pins the derivation, e.g. to keep avatars stable across
+    // releases that change the default
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    // light_on_dark: on a dark background, derive the foreground from a lighter
+    // luminance range so the pattern stands out
+    pub fn light_on_dark(mut self, light_on_dark: bool) -> Self {
+        self.light_on_dark = light_on_dark;
+        self
+    }
+
+    // palette: the foreground becomes the entry nearest in hue to the derived
+    // color; an empty palette leaves the derived color
+    pub fn palette(mut self, palette: color::Palette) -> Self {
+        self.palette = Some(palette);
+        self
+    }
+
+    #[must_use = "the identicon is only returned, not rendered"]
+    pub fn build(self) -> Result<Identicon> {
+        let (hash, paints, mut foreground) = match self.algorithm {
+            Algorithm::V1 => self.derive_v1()?,
+        };
+        if let Some(palette) = &self.palette {
+            if let Some(nearest) = palette.nearest_hue(foreground.as_hsl().hue()) {
+                foreground = nearest.clone();
+            }
+        }
+
+        let background = match self.derived_background {
+            true => {
+                color::HSL::new(foreground.as_hsl().hue(), DERIVED_BG_SAT, DERIVED_BG_LUM)?.as_rgb()
+            }
+            false => self.background,
+        };
+        let mut identicon = Identicon::from_parts(hash, paints, self.size, foreground, background);
+        match self.style.resolve(&hash) {
+            Style::Square | Style::Auto => {}
+            Style::Circle => identicon.shape = TileShape::Circle,
+            Style::Rounded => {
+                let radius = self.size / 4;
+                identicon.shape = TileShape::RoundedSquare { radius };
+            }
+            Style::Hex => identicon.layout = Layout::HexGrid,
+        }
+        identicon.checked_dimensions()?;
+        Ok(identicon)
+    }
+
+    fn derive_v1(&self) -> Result<(HashBytes, Paints, color::RGB)> {
+        let mut hash = self.hash.unwrap_or_else(|| self.hash_input());
+        let mut paints: Paints = [false; NUM_PAINTS];
+        self.paint(&hash, &mut paints);
+        if self.avoid_blank {
+            let mut rehashed = hash;
+            for _ in 0..MAX_REHASHES {
+                if paints.contains(&true) {
+                    break;
+                }
+                rehashed = Md5::digest(rehashed).into();
+                self.paint(&rehashed, &mut paints);
+            }
+            if paints.contains(&true) {
+                hash = rehashed;
+            }
+        }
+        let dark = self.background.relative_luminance() < DARK_LUMINANCE;
+        let lum = match self.light_on_dark && dark {
+            true => (DARK_BG_LUM_MIN, DARK_BG_LUM_MAX),
+            false => (LUM_MIN, LUM_MAX),
+        };
+        let hue = self.hue_fn.as_ref().map(|hue_fn| hue_fn(&hash));
+        let foreground = Identicon::compute_fg(&hash, self.direction, lum, hue)?;
+        Ok((hash, paints, foreground))
+    }
+
+    fn paint(&self, hash: &HashBytes, paints: &mut Paints) {
+        if self.center_weighted {
+            Identicon::paint_center_weighted(hash, paints);
+        } else {
+            Identicon::paint(hash, paints, &self.paint_rule);
+        }
+        if self.suppress_corners {
+            let num_cols = usize::from(NUM_SQUARES / 2);
+            let last_row = usize::from(NUM_SQUARES - 3);
+            // the left corners; mirroring clears the right ones
+            paints[0] = false;
+            paints[last_row * num_cols] = false;
+        }
+    }
+
+    fn hash_input(&self) -> HashBytes {
+        let mut hasher = Md5::new();
+        // length-prefixed so that domain, salt and name can't run into each
+        // other; the domain's length is tagged so it never reads as a salt
+        if let Some(domain) = &self.domain {
+            hasher.update((domain.len() as u64 | DOMAIN_TAG).to_be_bytes());
+            hasher.update(domain);
+        }
+        if let Some(salt) = &self.salt {
+            hasher.update((salt.len() as u64).to_be_bytes());
+            hasher.update(salt);
+        }
+        hasher.update(&self.input);
+        hasher.finalize().into()
+    }
+}
+
 impl Identicon {
     // name: the input string to generate identicon
     // size: the number of pixels of each square in the resulting image
     // background: the background color
+    #[must_use = "the identicon is only returned, not rendered"]
     pub fn new(name: &str, size: u32, background: color::RGB) -> Result<Self> {
-        let mut hasher = Md5::new();
-        hasher.update(name);
-        let hash: HashBytes = hasher.finalize().into();
-
-        let foreground = Self::compute_fg(&hash)?;
-        let mut paints: Paints = [false; 15];
-        Self::paint(&hash, &mut paints);
-
-        Ok(Self {
-            paints: paints,
-            size: size,
-            foreground: foreground,
-            background: background,
+        IdenticonBuilder::new(name, size, background).build()
+    }
+
+    // id: a numeric id hashed as its 8 big-endian bytes; same as from_u64_be
+    #[must_use = "the identicon is only returned, not rendered"]
+    pub fn from_u64(id: u64, size: u32, background: color::RGB) -> Result<Self> {
+        Self::from_u64_be(id, size, background)
+    }
+
+    // id: a numeric id hashed as its 8 big-endian bytes, on any platform
+    #[must_use = "the identicon is only returned, not rendered"]
+    pub fn from_u64_be(id: u64, size: u32, background: color::RGB) -> Result<Self> {
+        IdenticonBuilder::from_bytes(&id.to_be_bytes(), size, background).build()
+    }
+
+    // id: a numeric id hashed as its 8 little-endian bytes, on any platform
+    #[must_use = "the identicon is only returned, not rendered"]
+    pub fn from_u64_le(id: u64, size: u32, background: color::RGB) -> Result<Self> {
+        IdenticonBuilder::from_bytes(&id.to_le_bytes(), size, background).build()
+    }
+
+    // salt: mixed into the hash together with the name
+    #[must_use = "the identicon is only returned, not rendered"]
+    pub fn new_salted(name: &str, salt: &str, size: u32, background: color::RGB) -> Result<Self> {
+        IdenticonBuilder::new(name, size, background)
+            .salt(salt)
+            .build()
+    }
+
+    // a high-contrast identicon for low vision: black on white or, by the
+    // hash, white on black, with plain square cells and never blank; cells
+    // without a painted neighbor are dropped so that no single-cell specks
+    // remain, rehashing a hash left blank by that
+    #[must_use = "the identicon is only returned, not rendered"]
+    pub fn accessible(name: &str, size: u32) -> Result<Self> {
+        let (black, white) = (color::RGB::new(0, 0, 0), color::RGB::new(255, 255, 255));
+        let builder = || IdenticonBuilder::new(name, size, white.clone()).avoid_blank(true);
+        let mut identicon = builder().build()?;
+        identicon.drop_isolated_cells();
+        while identicon.is_blank() {
+            let hash = Md5::digest(identicon.hash).into();
+            identicon = builder().with_hash_bytes(hash).build()?;
+            identicon.drop_isolated_cells();
+        }
+        if identicon.hash[0] % 2 == 1 {
+            identicon.background = black;
+            identicon.set_foreground(white);
+        } else {
+            identicon.set_foreground(black);
+        }
+        Ok(identicon)
+    }
+
+    // the identicon stored by to_bytes, without the name it was derived from
+    #[must_use = "the identicon is only returned, not rendered"]
+    pub fn from_bytes_blob(blob: &[u8]) -> Result<Self> {
+        let invalid = |reason| Error::InvalidBlob { reason };
+        if blob.first() != Some(&BLOB_VERSION) {
+            return Err(invalid("unknown version"));
+        }
+        if blob.len() != BLOB_LEN {
+            return Err(invalid("wrong length"));
+        }
+        let mut hash: HashBytes = [0; 16];
+        hash.copy_from_slice(&blob[1..17]);
+        let paints = paints_from_u16(u16::from_be_bytes([blob[17], blob[18]]));
+        let u32_at =
+            |i: usize| u32::from_be_bytes([blob[i], blob[i + 1], blob[i + 2], blob[i + 3]]);
+        let (width, height) = (u32_at(19), u32_at(23));
+        let rgb_at = |i: usize| color::RGB::new(blob[i], blob[i + 1], blob[i + 2]);
+        let flags = blob[33];
+        if flags & !(BLOB_MIRROR | BLOB_RETRO | BLOB_TRANSPARENT | BLOB_GLYPH) != 0 {
+            return Err(invalid("unknown flags"));
+        }
+
+        let mut identicon = Self::from_parts(hash, paints, width, rgb_at(27), rgb_at(30));
+        identicon.height = height;
+        identicon.mirror = flags & BLOB_MIRROR != 0;
+        identicon.retro = flags & BLOB_RETRO != 0;
+        identicon.transparent = flags & BLOB_TRANSPARENT != 0;
+        identicon.glyph = flags & BLOB_GLYPH != 0;
+        identicon.checked_dimensions()?;
+        Ok(identicon)
+    }
+
+    // an identicon of the derived hash, paints and colors with every
+    // rendering option at its default
+    fn from_parts(
+        hash: HashBytes,
+        paints: Paints,
+        size: u32,
+        foreground: color::RGB,
+        background: color::RGB,
+    ) -> Self {
+        Self {
+            hash,
+            paints,
+            width: size,
+            height: size,
+            foreground,
+            background,
+            mirror: true,
+            accent: None,
+            padding: 0,
+            border: None,
+            jitter: 0,
+            opacity: 1.0,
+            shape: TileShape::default(),
+            layout: Layout::default(),
+            retro: false,
+            transparent: false,
+            shadow: None,
+            noise: 0,
+            glyph: false,
+            ring_colors: None,
+            rendered: Rendered::default(),
+        }
+    }
+
+    // whether png_bytes decodes to exactly the pixels of the identicon rendered
+    // from name, size and background
+    #[must_use = "the result tells whether the image matches"]
+    pub fn verify(name: &str, size: u32, background: color::RGB, png_bytes: &[u8]) -> Result<bool> {
+        let decoded = image::load_from_memory_with_format(png_bytes, image::ImageFormat::Png)
+            .map_err(|source| Error::Decode { source })?
+            .to_rgb8();
+        Ok(decoded == *Self::new(name, size, background)?.rendered())
+    }
+
+    // mirror: whether to reflect the painted cells onto the right half;
+    // turning it off shows the raw hash-to-paint mapping
+    pub fn set_mirror(&mut self, mirror: bool) {
+        self.mirror = mirror;
+        self.rendered.0.take();
+    }
+
+    // foreground: overrides the color derived from the hash
+    pub fn set_foreground(&mut self, foreground: color::RGB) {
+        self.foreground = foreground;
+        self.rendered.0.take();
+    }
+
+    pub fn foreground(&self) -> &color::RGB {
+        &self.foreground
+    }
+
+    pub fn background(&self) -> &color::RGB {
+        &self.background
+    }
+
+    #[must_use]
+    pub fn foreground_hsl(&self) -> color::HSL {
+        self.foreground.as_hsl()
+    }
+
+    // width, height: the number of pixels of each square horizontally and
+    // vertically, for rectangular squares; the mirror stays horizontal
+    pub fn set_size_wh(&mut self, width: u32, height: u32) -> Result<()> {
+        self.resize(|identicon| {
+            identicon.width = width;
+            identicon.height = height;
         })
     }
 
+    // accent: the color of the center column's middle cell, which is then
+    // painted regardless of the hash
+    pub fn set_accent(&mut self, accent: color::RGB) {
+        self.accent = Some(accent);
+        self.rendered.0.take();
+    }
+
+    // padding: the number of background pixels added around the grid
+    pub fn set_padding(&mut self, padding: u32) -> Result<()> {
+        self.resize(|identicon| identicon.padding = padding)
+    }
+
+    // width: the number of pixels of the border drawn around the padding
+    // color: the border color
+    pub fn set_border(&mut self, width: u32, color: color::RGB) -> Result<()> {
+        self.resize(|identicon| identicon.border = Some((width, color)))
+    }
+
     pub fn render(&self, path: &Path) -> Result<()> {
-        Ok(self.image().save(path)?)
+        let format: Option<OutputFormat> =
+            path.extension().and_then(|ext| ext.to_str()?.parse().ok());
+        match format {
+            Some(format) if self.transparent => self.render_as(path, format),
+            _ => self
+                .rendered()
+                .save(path)
+                .map_err(|err| Self::save_error(path, format, err)),
+        }
     }
 
-    // encode the formatted image using base64
-    fn format(&self, format: ImageOutputFormat) -> Result<String> {
+    // the error of saving to path, with i/o failures apart from those of the
+    // encoder for format
+    fn save_error(path: &Path, format: Option<OutputFormat>, err: ImageError) -> Error {
+        match (err, format) {
+            (ImageError::IoError(source), _) => Error::Io {
+                path: path.display().to_string(),
+                source,
+            },
+            (source, Some(format)) => Error::Encode {
+                format: format.name(),
+                source,
+            },
+            (source, None) => Error::SaveImage(source),
+        }
+    }
+
+    // render, first creating the missing directories of the path
+    pub fn render_create_dirs(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|source| Error::Io {
+                path: parent.display().to_string(),
+                source,
+            })?;
+        }
+        self.render(path)
+    }
+
+    // save the image in the given format regardless of the path's extension
+    pub fn render_as(&self, path: &Path, format: OutputFormat) -> Result<()> {
+        fs::write(path, self.bytes(format)?).map_err(|source| Error::Io {
+            path: path.display().to_string(),
+            source,
+        })
+    }
+
+    // render for fire-and-forget callers, silently ignoring failures; call
+    // render instead to find out about them
+    pub fn render_or_default(&self, path: &Path) {
+        let _ = self.render(path);
+    }
+
+    // the image encoded in the given format
+    fn bytes(&self, format: OutputFormat) -> Result<Vec<u8>> {
+        let mut bytes: Vec<u8> = Vec::new();
+        let encode_error = |source| Error::Encode {
+            format: format.name(),
+            source,
+        };
+        if self.transparent {
+            let img = self.rgba_image()?;
+            match format {
+                OutputFormat::Gif => GifEncoder::new(&mut bytes)
+                    .encode_frame(Frame::new(img))
+                    .map_err(encode_error)?,
+                OutputFormat::Png => img
+                    .write_to(&mut Cursor::new(&mut bytes), format.image_format())
+                    .map_err(encode_error)?,
+                OutputFormat::Jpeg => {
+                    return Err(Error::NoAlpha {
+                        format: format.name(),
+                    })
+                }
+            }
+        } else {
+            let mut cursor = Cursor::new(&mut bytes);
+            self.rendered()
+                .write_to(&mut cursor, format.image_format())
+                .map_err(encode_error)?;
+        }
+        Ok(bytes)
+    }
+
+    // a looping gif of frames frames, each shown for delay_ms, with the image
+    // scaled from min_scale up to max_scale and back, centered on a canvas
+    // sized for the larger scale
+    #[must_use = "the encoded image is only returned, not saved"]
+    pub fn animated_pulse_gif(
+        &self,
+        frames: u32,
+        delay_ms: u32,
+        min_scale: f32,
+        max_scale: f32,
+    ) -> Result<Vec<u8>> {
+        let encode_error = |source| Error::Encode {
+            format: OutputFormat::Gif.name(),
+            source,
+        };
+        let img = self.rendered();
+        let (width, height) = img.dimensions();
+        let scaled = |len: u32, scale: f32| ((len as f32 * scale.max(0.0)).round() as u32).max(1);
+        let canvas_scale = min_scale.max(max_scale);
+        let (canvas_width, canvas_height) =
+            (scaled(width, canvas_scale), scaled(height, canvas_scale));
+        let delay = Delay::from_numer_denom_ms(delay_ms, 1);
+
+        let frames = (0..frames.max(1)).map(|i| {
+            // eases in and out along a cosine, starting from min_scale
+            let phase = i as f32 / frames.max(1) as f32 * std::f32::consts::TAU;
+            let scale = min_scale + (max_scale - min_scale) * (1.0 - phase.cos()) / 2.0;
+            let (w, h) = (scaled(width, scale), scaled(height, scale));
+            let resized = imageops::resize(img, w, h, imageops::FilterType::Nearest);
+            let mut canvas =
+                RgbImage::from_pixel(canvas_width, canvas_height, self.background.as_pixel());
+            let x = (i64::from(canvas_width) - i64::from(w)) / 2;
+            let y = (i64::from(canvas_height) - i64::from(h)) / 2;
+            imageops::overlay(&mut canvas, &resized, x, y);
+            let rgba = image::DynamicImage::ImageRgb8(canvas).into_rgba8();
+            Frame::from_parts(rgba, 0, 0, delay)
+        });
+
         let mut bytes: Vec<u8> = Vec::new();
-        let mut cursor = Cursor::new(&mut bytes);
-        self.image().write_to(&mut cursor, format)?;
-        Ok(base64::encode(&bytes))
+        let mut encoder = GifEncoder::new(&mut bytes);
+        encoder.set_repeat(Repeat::Infinite).map_err(encode_error)?;
+        encoder.encode_frames(frames).map_err(encode_error)?;
+        drop(encoder);
+        Ok(bytes)
+    }
+
+    // the image with its background pixels fully transparent
+    fn rgba_image(&self) -> Result<RgbaImage> {
+        if self.foreground == self.background {
+            return Err(Error::TransparentForeground);
+        }
+        let background = self.background.as_pixel();
+        let img = self.rendered();
+        Ok(RgbaImage::from_fn(img.width(), img.height(), |x, y| {
+            let pixel = img[(x, y)];
+            let alpha = if pixel == background { 0 } else { 255 };
+            Rgba([pixel[0], pixel[1], pixel[2], alpha])
+        }))
+    }
+
+    // the encoded image together with its MIME type, e.g. for http responses
+    #[must_use = "the encoded image is only returned, not saved"]
+    pub fn render_to_vec(&self, format: OutputFormat) -> Result<(Vec<u8>, &'static str)> {
+        Ok((self.bytes(format)?, format.mime_type()))
+    }
+
+    // the png bytes behind a reader, for apis taking an io::Read
+    #[must_use = "the encoded image is only returned, not saved"]
+    pub fn png_reader(&self) -> Result<impl Read> {
+        Ok(Cursor::new(self.bytes(OutputFormat::Png)?))
+    }
+
+    // the image encoded in each of formats, in order, all from one render
+    #[must_use = "the encoded images are only returned, not saved"]
+    pub fn encode_all(&self, formats: &[OutputFormat]) -> Result<Vec<(OutputFormat, Vec<u8>)>> {
+        formats
+            .iter()
+            .map(|&format| Ok((format, self.bytes(format)?)))
+            .collect()
+    }
+
+    // encode the formatted image using base64
+    #[must_use = "the encoded image is only returned, not saved"]
+    pub fn encode(&self, format: OutputFormat) -> Result<String> {
+        Ok(base64::encode(&self.bytes(format)?))
     }
 
+    #[must_use = "the encoded image is only returned, not saved"]
     pub fn png(&self) -> Result<String> {
-        self.format(ImageOutputFormat::Png)
+        self.encode(OutputFormat::Png)
     }
 
-    pub fn jpeg(&self) -> Result <String> {
-        self.format(ImageOutputFormat::Jpeg(JPEG_QUALITY))
+    #[must_use = "the encoded image is only returned, not saved"]
+    pub fn jpeg(&self) -> Result<String> {
+        self.encode(OutputFormat::Jpeg)
     }
 
+    #[must_use = "the encoded image is only returned, not saved"]
     pub fn gif(&self) -> Result<String> {
-        self.format(ImageOutputFormat::Gif)
+        self.encode(OutputFormat::Gif)
     }
 
-    fn image(&self) -> RgbImage {
-        let size = self.size * u32::from(NUM_SQUARES);
-        let mut img = RgbImage::from_pixel(size, size, self.background.as_pixel());
-        let num_center_cols = NUM_SQUARES / 2;
-        for (i, paint) in self.paints.iter().enumerate() {
-            let row = 1 + i as u8 / num_center_cols;
-            let col = 1 + i as u8 % num_center_cols;
-            let row_pixel = u32::from(row) * self.size;
-            let col_pixel = u32::from(col) * self.size;
-
-            for x in col_pixel..col_pixel + self.size {
-                for y in row_pixel..row_pixel + self.size {
-                    if *paint {
-                        img.put_pixel(x, y, self.foreground.as_pixel());
-                        img.put_pixel(size - 1 - x, y, self.foreground.as_pixel());
-                    }
-                }
+    // jitter: the maximum number of pixels each cell is shifted by, in both
+    // directions, using an amount derived from the hash
+    pub fn set_jitter(&mut self, jitter: u8) {
+        self.jitter = jitter;
+        self.rendered.0.take();
+    }
+
+    // a reproducible byte stream seeded from the hash, for deriving custom
+    // features; the same name always yields the same stream
+    pub fn rng(&self) -> impl Iterator<Item = u8> {
+        rng::Xorshift::new(&self.hash)
+    }
+
+    // opacity: in [0, 1], how opaque painted cells are drawn over the background
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.opacity = opacity.clamp(0.0, 1.0);
+        self.rendered.0.take();
+    }
+
+    // shape: how each painted cell is drawn
+    pub fn set_shape(&mut self, shape: TileShape) {
+        self.shape = shape;
+        self.rendered.0.take();
+    }
+
+    // shift the foreground's luminance away from the background, one step at a
+    // time, until their contrast ratio reaches min_ratio or the luminance
+    // hits black or white
+    pub fn ensure_contrast(&mut self, min_ratio: f32) -> Result<()> {
+        let hsl = self.foreground.as_hsl();
+        let lighten = self.background.relative_luminance() < DARK_LUMINANCE;
+        let mut lum = hsl.lum();
+        while self.foreground.contrast_ratio(&self.background) < min_ratio {
+            lum = if lighten {
+                (lum + 1.0).min(f32::from(color::LUM_MAX))
+            } else {
+                (lum - 1.0).max(0.0)
+            };
+            self.set_foreground(color::HSL::new(hsl.hue(), hsl.sat(), lum)?.as_rgb());
+            if lum == 0.0 || lum == f32::from(color::LUM_MAX) {
+                break;
             }
         }
+        Ok(())
+    }
 
-        img
+    // retro: draw each painted cell as a 2x2 checkerboard of its color and a
+    // darker shade
+    pub fn set_retro(&mut self, retro: bool) {
+        self.retro = retro;
+        self.rendered.0.take();
     }
 
-    fn compute_fg(hash: &HashBytes) -> Result<color::RGB> {
-        let h1 = (u16::from(hash[12]) & 0x0f) << 8;
-        let h2 = u16::from(hash[13]);
+    // transparent: make background pixels transparent in png and gif output;
+    // encoding fails for jpeg, or if the foreground equals the background
+    pub fn set_transparent(&mut self, transparent: bool) {
+        self.transparent = transparent;
+        self.rendered.0.take();
+    }
 
-        let hue = h1 | h2; // max 12 bits
-        let sat = hash[14];
-        let lum = hash[15];
+    // dx, dy: the offset in pixels of a shadow cast by the painted cells in
+    // color, drawn beneath them and clipped to the image
+    pub fn set_shadow(&mut self, dx: i32, dy: i32, color: color::RGB) {
+        self.shadow = Some((dx, dy, color));
+        self.rendered.0.take();
+    }
 
-        let hue = Self::map(f32::from(hue), 0.0, 4095.0, 0.0, f32::from(color::HUE_MAX));
-        let sat = Self::map(
-            f32::from(sat),
-            0.0,
-            255.0,
-            f32::from(SAT_MAX),
-            f32::from(SAT_MIN),
-        );
-        let lum = Self::map(
-            f32::from(lum),
-            0.0,
-            255.0,
-            f32::from(LUM_MAX),
-            f32::from(LUM_MIN),
-        );
+    // intensity: how far each background pixel's channels may stray from the
+    // background color, as a texture seeded from the hash; ignored when the
+    // background is transparent
+    pub fn set_noisy_background(&mut self, intensity: u8) {
+        self.noise = intensity;
+        self.rendered.0.take();
+    }
 
-        Ok(color::HSL::new(hue, sat, lum)?.as_rgb())
+    // ring_colors: give each ring of cells around the center its own color,
+    // derived like the foreground from the hash rotated by a few bytes per
+    // ring; the outermost painted ring keeps the foreground
+    pub fn set_ring_colors(&mut self, ring_colors: bool) -> Result<()> {
+        self.ring_colors = match ring_colors {
+            true => {
+                let rings = 2..=NUM_SQUARES / 2;
+                let colors = rings.map(|ring| {
+                    let mut hash = self.hash;
+                    hash.rotate_left(usize::from(ring) * 4);
+                    Self::compute_fg(&hash, Direction::default(), (LUM_MIN, LUM_MAX), None)
+                });
+                Some(colors.collect::<Result<_>>()?)
+            }
+            false => None,
+        };
+        self.rendered.0.take();
+        Ok(())
     }
 
-    // linearly map val in [vmin, vmax] to [dmin, dmax]
-    fn map(val: f32, vmin: f32, vmax: f32, dmin: f32, dmax: f32) -> f32 {
-        dmin + ((val - vmin) * (dmax - dmin)) / (vmax - vmin)
+    // center_glyph: draw a hash-chosen glyph in the center cell, in the
+    // background color over a painted cell and in the foreground color
+    // otherwise; not drawn in the hex layout
+    pub fn set_center_glyph(&mut self, center_glyph: bool) {
+        self.glyph = center_glyph;
+        self.rendered.0.take();
     }
 
-    fn paint(hash: &HashBytes, paints: &mut Paints) {
-        let len = paints.len();
-        let nibbles = hash
+    // the glyph drawn in the center cell, if enabled
+    #[must_use]
+    pub fn center_glyph(&self) -> Option<Glyph> {
+        let glyph = Glyph::ALL[usize::from(self.hash[GLYPH_BYTE]) % Glyph::ALL.len()];
+        self.glyph.then_some(glyph)
+    }
+
+    // layout: how cells are arranged on the canvas
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.layout = layout;
+        self.rendered.0.take();
+    }
+
+    // whether both identicons share the same pattern and foreground, i.e. are
+    // visually indistinguishable apart from size, background and decorations
+    #[must_use]
+    pub fn collides_with(&self, other: &Identicon) -> bool {
+        self.paints == other.paints && self.foreground == other.foreground
+    }
+
+    // the md5 hash the identicon was derived from, as lowercase hex
+    #[must_use]
+    pub fn fingerprint(&self) -> String {
+        self.hash.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    // a compact blob for caching, read back by from_bytes_blob: a version byte,
+    // the hash, the paints, cell width and height, foreground, background and
+    // flags; other rendering options such as padding or border are not kept
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let flag = |set: bool, bit: u8| if set { bit } else { 0 };
+        let flags = flag(self.mirror, BLOB_MIRROR)
+            | flag(self.retro, BLOB_RETRO)
+            | flag(self.transparent, BLOB_TRANSPARENT)
+            | flag(self.glyph, BLOB_GLYPH);
+        let mut blob = Vec::with_capacity(BLOB_LEN);
+        blob.push(BLOB_VERSION);
+        blob.extend(self.hash);
+        blob.extend(paints_to_u16(&self.paints).to_be_bytes());
+        blob.extend(self.width.to_be_bytes());
+        blob.extend(self.height.to_be_bytes());
+        blob.extend(self.foreground.as_pixel().0);
+        blob.extend(self.background.as_pixel().0);
+        blob.push(flags);
+        blob
+    }
+
+    // a file name from the fingerprint and the format's extension, e.g. for
+    // storing avatars by content; it doesn't capture rendering options
+    #[must_use]
+    pub fn content_name(&self, format: OutputFormat) -> String {
+        format!("{}.{}", self.fingerprint(), format.extension())
+    }
+
+    // in [0, 1], the mean of the share of paint cells both agree on and how
+    // close the foregrounds are in rgb space; 1 for identical patterns and colors
+    #[must_use]
+    pub fn similarity(&self, other: &Identicon) -> f32 {
+        let matching = self
+            .paints
             .iter()
-            .flat_map(|b| [(b & 0xf0) >> 4, (b & 0x0f)])
-            .take(len);
+            .zip(&other.paints)
+            .filter(|(a, b)| a == b)
+            .count();
+        let paints = matching as f32 / NUM_PAINTS as f32;
 
-        let num_cols = usize::from(NUM_SQUARES / 2);
-        let num_rows = usize::from(NUM_SQUARES - 2);
-        for (i, nibble) in nibbles.enumerate() {
-            let col = 2 - i / num_rows;
-            let row = i % num_rows;
-            let idx = row * num_cols + col;
-            paints[idx] = nibble % 2 == 0;
+        let [r1, g1, b1] = self.foreground.as_pixel().0.map(f32::from);
+        let [r2, g2, b2] = other.foreground.as_pixel().0.map(f32::from);
+        let distance = ((r1 - r2).powi(2) + (g1 - g2).powi(2) + (b1 - b2).powi(2)).sqrt();
+        let colors = 1.0 - distance / (3.0 * 255.0f32.powi(2)).sqrt();
+        (paints + colors) / 2.0
+    }
+
+    // width and height of the image in pixels, without rendering it
+    #[must_use]
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.checked_dimensions().expect(FITS)
+    }
+
+    // dimensions, or TooLarge if they overflow u32; checked wherever the
+    // size, padding or border is set
+    fn checked_dimensions(&self) -> Result<(u32, u32)> {
+        let (width, height) = self.grid_dimensions()?;
+        let margin = self.offset()?.checked_mul(2).ok_or(Error::TooLarge)?;
+        let grow = |len: u32| len.checked_add(margin).ok_or(Error::TooLarge);
+        Ok((grow(width)?, grow(height)?))
+    }
+
+    // width and height of the grid in pixels, excluding padding and border
+    fn grid_dimensions(&self) -> Result<(u32, u32)> {
+        let num_squares = u32::from(NUM_SQUARES);
+        let scale = |len: u32| len.checked_mul(num_squares).ok_or(Error::TooLarge);
+        Ok((scale(self.width)?, scale(self.height)?))
+    }
+
+    // distance in pixels from the image edge to the grid
+    fn offset(&self) -> Result<u32> {
+        let border_width = self.border.as_ref().map_or(0, |(width, _)| *width);
+        self.padding
+            .checked_add(border_width)
+            .ok_or(Error::TooLarge)
+    }
+
+    // apply update to the size, padding or border, undoing it if the
+    // dimensions would overflow
+    fn resize(&mut self, update: impl FnOnce(&mut Self)) -> Result<()> {
+        let saved = (self.width, self.height, self.padding, self.border.clone());
+        update(self);
+        if let Err(err) = self.checked_dimensions() {
+            (self.width, self.height, self.padding, self.border) = saved;
+            return Err(err);
+        }
+        self.rendered.0.take();
+        Ok(())
+    }
+
+    // png bytes carrying a tEXt chunk, e.g. to record the source name
+    #[must_use = "the encoded image is only returned, not saved"]
+    pub fn png_with_metadata(&self, key: &str, value: &str) -> Result<Vec<u8>> {
+        let img = self.rendered();
+        let mut bytes: Vec<u8> = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, img.width(), img.height());
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.add_text_chunk(key.to_string(), value.to_string())?;
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(img.as_raw())?;
+        writer.finish()?;
+        Ok(bytes)
+    }
+
+    // binary ppm bytes written straight from the grid without the image
+    // encoders; cells are plain squares, so shape, layout, jitter, opacity,
+    // retro style and shadow are not drawn
+    #[must_use]
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let (width, height) = self.dimensions();
+        let (grid_width, grid_height) = self.grid_dimensions().expect(FITS);
+        let offset = self.offset().expect(FITS);
+        let grid = self.grid();
+        let center = NUM_SQUARES / 2;
+
+        let mut bytes = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+        for y in 0..height {
+            for x in 0..width {
+                let (gx, gy) = (x.wrapping_sub(offset), y.wrapping_sub(offset));
+                let color = if gx < grid_width && gy < grid_height {
+                    let (row, col) = ((gy / self.height) as u8, (gx / self.width) as u8);
+                    let accented = self.accent.is_some() && row == center && col == center;
+                    if accented || grid[usize::from(row)][usize::from(col)] {
+                        self.cell_color(row, col)
+                    } else {
+                        &self.background
+                    }
+                } else {
+                    match &self.border {
+                        Some((border, color))
+                            if x < *border
+                                || y < *border
+                                || x >= width - border
+                                || y >= height - border =>
+                        {
+                            color
+                        }
+                        _ => &self.background,
+                    }
+                };
+                bytes.extend(color.as_pixel().0);
+            }
+        }
+        bytes
+    }
+
+    // png bytes that depend only on the identicon: fixed encoder settings and
+    // no chunks besides the image header, data and end, so no time or metadata
+    #[must_use = "the encoded image is only returned, not saved"]
+    pub fn png_reproducible(&self) -> Result<Vec<u8>> {
+        let img = self.rendered();
+        let mut bytes: Vec<u8> = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, img.width(), img.height());
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_compression(png::Compression::Default);
+        encoder.set_filter(png::FilterType::Sub);
+        encoder.set_adaptive_filter(png::AdaptiveFilterType::NonAdaptive);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(img.as_raw())?;
+        writer.finish()?;
+        Ok(bytes)
+    }
+
+    // an ico holding one image per entry of sizes, each the full image scaled
+    // to that many pixels square with nearest-neighbor; ico images are at
+    // most 256 pixels square, and rgba since decoders reject rgb png entries
+    #[must_use = "the encoded image is only returned, not saved"]
+    pub fn ico_multi(&self, sizes: &[u32]) -> Result<Vec<u8>> {
+        let encode_error = |source| Error::Encode {
+            format: "ico",
+            source,
+        };
+        let img = match self.transparent {
+            true => self.rgba_image()?,
+            false => DynamicImage::from(self.rendered().clone()).into_rgba8(),
+        };
+        let frames = sizes
+            .iter()
+            .map(|&size| {
+                let scaled = imageops::resize(&img, size, size, imageops::FilterType::Nearest);
+                IcoFrame::as_png(scaled.as_raw(), size, size, ColorType::Rgba8)
+            })
+            .collect::<result::Result<Vec<_>, _>>()
+            .map_err(encode_error)?;
+        let mut bytes = Vec::new();
+        IcoEncoder::new(&mut bytes)
+            .encode_images(&frames)
+            .map_err(encode_error)?;
+        Ok(bytes)
+    }
+
+    // png bytes with a palette of just the colors used, at the smallest bit
+    // depth that fits them; much smaller than truecolor for so few colors
+    #[must_use = "the encoded image is only returned, not saved"]
+    pub fn png_indexed(&self) -> Result<Vec<u8>> {
+        let img = self.rendered();
+        let mut palette: Vec<Rgb<u8>> = Vec::new();
+        let indices: Vec<usize> = img
+            .pixels()
+            .map(|pixel| match palette.iter().position(|p| p == pixel) {
+                Some(i) => i,
+                None => {
+                    palette.push(*pixel);
+                    palette.len() - 1
+                }
+            })
+            .collect();
+        let (depth, bits) = match palette.len() {
+            0..=2 => (png::BitDepth::One, 1),
+            3..=4 => (png::BitDepth::Two, 2),
+            5..=16 => (png::BitDepth::Four, 4),
+            17..=256 => (png::BitDepth::Eight, 8),
+            // more colors than any identicon draws; keep truecolor then
+            _ => return self.bytes(OutputFormat::Png),
+        };
+
+        // pack each row most significant bits first, padding it to whole bytes
+        let width = img.width() as usize;
+        let mut data: Vec<u8> = Vec::new();
+        for row in indices.chunks(width.max(1)) {
+            let mut packed = vec![0u8; (width * bits).div_ceil(8)];
+            for (x, index) in row.iter().enumerate() {
+                let shift = 8 - bits - (x * bits) % 8;
+                packed[x * bits / 8] |= (*index as u8) << shift;
+            }
+            data.extend(packed);
+        }
+
+        let mut bytes: Vec<u8> = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, img.width(), img.height());
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(depth);
+        // filtering only helps with smooth gradients, not packed palette indices
+        encoder.set_filter(png::FilterType::NoFilter);
+        encoder.set_compression(png::Compression::Best);
+        encoder.set_palette(palette.iter().flat_map(|p| p.0).collect::<Vec<u8>>());
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&data)?;
+        writer.finish()?;
+        Ok(bytes)
+    }
+
+    fn image(&self) -> RgbImage {
+        self.rendered().clone()
+    }
+
+    // the image, rendered on first use and kept until a setter changes it
+    fn rendered(&self) -> &RgbImage {
+        self.rendered.0.get_or_init(|| {
+            let (_, height) = self.dimensions();
+            self.band(0..height)
+        })
+    }
+
+    // the image one row at a time, each drawn on its own, so that large images
+    // can be processed without holding all of their pixels
+    pub fn rows(&self) -> impl Iterator<Item = Vec<Rgb<u8>>> + '_ {
+        let (_, height) = self.dimensions();
+        (0..height).map(|y| self.band(y..y + 1).pixels().copied().collect())
+    }
+
+    // the image rows in window, as an image of just those rows
+    fn band(&self, window: Range<u32>) -> RgbImage {
+        let (width, _) = self.dimensions();
+        let rows = window.len() as u32;
+        let mut img = RgbImage::new(width, rows);
+        let mut pixels = PixelRows {
+            bytes: &mut img,
+            stride: width as usize * 3,
+            width,
+            rows,
+        };
+        self.draw(window, &mut pixels);
+        img
+    }
+
+    // draw the image rows in window into pixels, whose first row is the
+    // window's first
+    fn draw(&self, window: Range<u32>, img: &mut PixelRows) {
+        let (width, height) = self.dimensions();
+        let top = window.start;
+        for y in 0..img.rows {
+            let iy = top + y;
+            for x in 0..width {
+                let pixel = match &self.border {
+                    Some((border, color))
+                        if x < *border
+                            || iy < *border
+                            || x >= width - border
+                            || iy >= height - border =>
+                    {
+                        color.as_pixel()
+                    }
+                    _ if self.noise == 0 || self.transparent => self.background.as_pixel(),
+                    _ => self.noisy_background(x, iy),
+                };
+                img.put(x, y, pixel);
+            }
+        }
+
+        if let Some((dx, dy, shadow)) = &self.shadow {
+            // the rows whose shadows fall into the window
+            let shift =
+                |y: u32| (i64::from(y) - i64::from(*dy)).clamp(0, i64::from(u32::MAX)) as u32;
+            let cast = |x: u32, y: u32| {
+                let sy = y.checked_add_signed(*dy)?.checked_sub(top)?;
+                Some((x.checked_add_signed(*dx)?, sy))
+            };
+            self.for_each_painted_in(shift(window.start)..shift(window.end), |x, y, _| {
+                if let Some((sx, sy)) = cast(x, y) {
+                    img.put(sx, sy, shadow.as_pixel());
+                }
+            });
+        }
+        self.for_each_painted_in(window.clone(), |x, y, color| {
+            let color = color.blend(&self.background, self.opacity);
+            img.put(x, y - top, color.as_pixel())
+        });
+        self.draw_center_glyph(img, window);
+    }
+
+    // draw the center glyph onto img, which holds the image rows in window
+    fn draw_center_glyph(&self, img: &mut PixelRows, window: Range<u32>) {
+        let glyph = match self.center_glyph() {
+            Some(glyph) if self.layout == Layout::Grid => glyph,
+            _ => return,
+        };
+        let top = window.start;
+        let color = match self.is_painted(Self::center_index()) {
+            true => &self.background,
+            false => &self.foreground,
+        };
+        let center = u32::from(NUM_SQUARES / 2);
+        let (x0, y0) = (center * self.width, center * self.height);
+        let offset = self.offset().expect(FITS);
+        let window = Self::grid_window(window, offset);
+        let rows = y0.max(window.start)..(y0 + self.height).min(window.end);
+        for x in x0..x0 + self.width {
+            for y in rows.clone() {
+                if glyph.covers(x - x0, y - y0, self.width, self.height) {
+                    let (px, py) = (offset + x, offset + y - top);
+                    img.put(px, py, color.as_pixel());
+                }
+            }
+        }
+    }
+
+    // draw the image as rgb bytes into the top-left of a caller's width x
+    // height framebuffer whose rows start stride bytes apart, straight into
+    // buf without rendering or caching an image; bytes outside the image are
+    // left untouched
+    pub fn render_into(
+        &self,
+        buf: &mut [u8],
+        stride: usize,
+        width: u32,
+        height: u32,
+    ) -> Result<()> {
+        let invalid = |reason| Err(Error::InvalidBuffer { reason });
+        let (img_width, img_height) = self.dimensions();
+        if img_width > width || img_height > height {
+            return invalid("the image is larger than the framebuffer");
+        }
+        let row_len = width as usize * 3;
+        if stride < row_len {
+            return invalid("the stride is shorter than a row");
+        }
+        let needed = match height {
+            0 => Some(0),
+            _ => stride
+                .checked_mul(height as usize - 1)
+                .and_then(|len| len.checked_add(row_len)),
+        };
+        if needed.is_none_or(|needed| buf.len() < needed) {
+            return invalid("the buffer is too small for the framebuffer");
+        }
+        let mut pixels = PixelRows {
+            bytes: buf,
+            stride,
+            width: img_width,
+            rows: img_height,
+        };
+        self.draw(0..img_height, &mut pixels);
+        Ok(())
+    }
+
+    // draw the painted cells onto canvas with the identicon's top-left corner
+    // at (x, y), leaving background pixels of the canvas untouched; cells
+    // blend over the canvas as it was, so pixels drawn twice, as in the
+    // mirrored center column, aren't blended twice
+    pub fn render_onto(&self, canvas: &mut RgbImage, x: u32, y: u32) {
+        let (width, height) = self.dimensions();
+        let original = imageops::crop_imm(canvas, x, y, width, height).to_image();
+        self.for_each_painted(|px, py, color| {
+            let (cx, cy) = (x.saturating_add(px), y.saturating_add(py));
+            if let Some(pixel) = canvas.get_pixel_mut_checked(cx, cy) {
+                let [r, g, b] = original[(px, py)].0;
+                *pixel = color
+                    .blend(&color::RGB::new(r, g, b), self.opacity)
+                    .as_pixel();
+            }
+        });
+    }
+
+    // black painted cells on white, ignoring the computed colors
+    #[must_use]
+    pub fn to_mono(&self) -> GrayImage {
+        let (width, height) = self.dimensions();
+        let mut img = GrayImage::from_pixel(width, height, Luma([255]));
+        self.for_each_painted(|x, y, _| img.put_pixel(x, y, Luma([0])));
+        img
+    }
+
+    // the identicon rendered with one pixel per square and blown up by scale
+    // with nearest-neighbor sampling, for a pixelated look; padding and border
+    // are dropped, since they don't come in whole squares
+    #[must_use = "the preview is only returned, not saved"]
+    pub fn preview(&self, scale: u32) -> Result<RgbImage> {
+        let mut tiny = self.clone();
+        tiny.padding = 0;
+        tiny.border = None;
+        tiny.set_size_wh(1, 1)?;
+        let img = tiny.rendered();
+        let (width, height) = img.dimensions();
+        let scaled = |len: u32| len.checked_mul(scale).ok_or(Error::TooLarge);
+        Ok(imageops::resize(
+            img,
+            scaled(width)?,
+            scaled(height)?,
+            imageops::FilterType::Nearest,
+        ))
+    }
+
+    // the rendered image scaled down with filter so that its longer side is
+    // size pixels, keeping its aspect ratio with the shorter side at least a
+    // pixel; a size of 0 gives an empty image, and an empty image a plain
+    // background square of size pixels
+    #[must_use]
+    pub fn thumbnail(&self, size: u32, filter: FilterType) -> RgbImage {
+        if size == 0 {
+            return RgbImage::new(0, 0);
+        }
+        let img = self.rendered();
+        let (width, height) = img.dimensions();
+        let longer = width.max(height);
+        if longer == 0 {
+            return RgbImage::from_pixel(size, size, self.background.as_pixel());
+        }
+        let scaled =
+            |len: u32| (u64::from(len) * u64::from(size) / u64::from(longer)).max(1) as u32;
+        imageops::resize(img, scaled(width), scaled(height), filter)
+    }
+
+    // the image's channels scaled to [0, 1], row-major with shape
+    // height x width x 3, together with the width and height
+    #[must_use]
+    pub fn to_f32_buffer(&self) -> (Vec<f32>, u32, u32) {
+        let img = self.rendered();
+        let buffer = img.as_raw().iter().map(|&c| f32::from(c) / 255.0).collect();
+        (buffer, img.width(), img.height())
+    }
+
+    // the image cut to the circle inscribed in its shorter side, transparent
+    // outside; edge pixels are as opaque as the circle covers them, so the
+    // boundary is anti-aliased
+    #[must_use]
+    pub fn circular(&self) -> RgbaImage {
+        let img = self.rendered();
+        let (width, height) = img.dimensions();
+        let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+        let radius = cx.min(cy);
+        let background = self.background.as_pixel();
+        RgbaImage::from_fn(width, height, |x, y| {
+            let pixel = img[(x, y)];
+            let distance = (x as f32 + 0.5 - cx).hypot(y as f32 + 0.5 - cy);
+            let coverage = (radius - distance + 0.5).clamp(0.0, 1.0);
+            let opaque = !(self.transparent && pixel == background);
+            let alpha = if opaque {
+                (coverage * 255.0).round() as u8
+            } else {
+                0
+            };
+            Rgba([pixel[0], pixel[1], pixel[2], alpha])
+        })
+    }
+
+    // repeat_x by repeat_y copies of the image that tile seamlessly: every
+    // other column of copies is flipped horizontally and every other row
+    // vertically, so each copy meets its neighbors edge to matching edge
+    #[must_use = "the tiled image is only returned, not saved"]
+    pub fn tileable(&self, repeat_x: u32, repeat_y: u32) -> Result<RgbImage> {
+        let img = self.rendered();
+        let (width, height) = img.dimensions();
+        let repeat = |len: u32, times: u32| len.checked_mul(times).ok_or(Error::TooLarge);
+        let (tiled_width, tiled_height) = (repeat(width, repeat_x)?, repeat(height, repeat_y)?);
+        Ok(RgbImage::from_fn(tiled_width, tiled_height, |x, y| {
+            let (tx, ty) = (x / width, y / height);
+            let (mut x, mut y) = (x % width, y % height);
+            if tx % 2 == 1 {
+                x = width - 1 - x;
+            }
+            if ty % 2 == 1 {
+                y = height - 1 - y;
+            }
+            img[(x, y)]
+        }))
+    }
+
+    // the rendered image turned clockwise by quarter_turns * 90 degrees
+    #[must_use]
+    pub fn rotate(&self, quarter_turns: u8) -> RgbImage {
+        let img = self.image();
+        match quarter_turns % 4 {
+            1 => imageops::rotate90(&img),
+            2 => imageops::rotate180(&img),
+            3 => imageops::rotate270(&img),
+            _ => img,
+        }
+    }
+
+    // the rendered image mirrored left to right
+    #[must_use]
+    pub fn flip_horizontal(&self) -> RgbImage {
+        imageops::flip_horizontal(self.rendered())
+    }
+
+    // the rendered image mirrored top to bottom
+    #[must_use]
+    pub fn flip_vertical(&self) -> RgbImage {
+        imageops::flip_vertical(self.rendered())
+    }
+
+    // (row, col) of every painted cell in the full grid, mirrored
+    // positions included
+    pub fn painted_cells(&self) -> impl Iterator<Item = (u8, u8)> + '_ {
+        (0..NUM_PAINTS)
+            .filter(|&i| self.is_painted(i))
+            .flat_map(|i| {
+                let (row, col) = Self::cell_position(i, NUM_SQUARES);
+                let mirrored = NUM_SQUARES - 1 - col;
+                let mirror = (self.mirror && mirrored != col).then_some((row, mirrored));
+                iter::once((row, col)).chain(mirror)
+            })
+    }
+
+    // unpaint the cells with no painted cell above, below, left or right;
+    // dropping them leaves every other cell's neighbors as they were
+    fn drop_isolated_cells(&mut self) {
+        let grid = self.grid();
+        let painted = |row: u8, col: u8| {
+            grid.get(usize::from(row))
+                .and_then(|cells| cells.get(usize::from(col)))
+                == Some(&true)
+        };
+        for i in 0..NUM_PAINTS {
+            let (row, col) = Self::cell_position(i, NUM_SQUARES);
+            let neighbors = [
+                (row - 1, col),
+                (row + 1, col),
+                (row, col - 1),
+                (row, col + 1),
+            ];
+            if !neighbors.iter().any(|&(row, col)| painted(row, col)) {
+                self.paints[i] = false;
+            }
+        }
+        self.rendered.0.take();
+    }
+
+    // whether no cell is painted, leaving just the background
+    #[must_use]
+    pub fn is_blank(&self) -> bool {
+        self.painted_cells().next().is_none()
+    }
+
+    // mean color of the image, weighing each color by the area the grid,
+    // padding and border give it; cells count as plain squares as in to_ppm
+    #[must_use]
+    pub fn average_color(&self) -> color::RGB {
+        let (width, height) = self.dimensions();
+        let total = u64::from(width) * u64::from(height);
+        let cell = u64::from(self.width) * u64::from(self.height);
+        let mut areas: Vec<(&color::RGB, u64)> = Vec::new();
+        for (row, col) in self.painted_cells() {
+            areas.push((self.cell_color(row, col), cell));
+        }
+        if let Some((border, color)) = &self.border {
+            let inner = |len: u32| u64::from(len.saturating_sub(2 * border));
+            areas.push((color, total - inner(width) * inner(height)));
+        }
+        let covered: u64 = areas.iter().map(|(_, area)| area).sum();
+        areas.push((&self.background, total - covered));
+
+        let channel = |i: usize| {
+            let sum: u64 = areas
+                .iter()
+                .map(|(color, area)| u64::from(color.as_pixel().0[i]) * area)
+                .sum();
+            ((sum + total / 2) / total) as u8
+        };
+        color::RGB::new(channel(0), channel(1), channel(2))
+    }
+
+    // the full grid of painted cells, indexed by [row][col]
+    #[must_use]
+    pub fn grid(&self) -> [[bool; GRID_SIZE]; GRID_SIZE] {
+        let mut grid = [[false; GRID_SIZE]; GRID_SIZE];
+        for (row, col) in self.painted_cells() {
+            grid[usize::from(row)][usize::from(col)] = true;
+        }
+        grid
+    }
+
+    // css declarations for an element px pixels square at the top-left of the
+    // grid; each painted cell is one box-shadow in its color, over a last
+    // shadow spread across the whole grid in the background color, which
+    // together with the element's own background fills the grid
+    #[must_use]
+    pub fn to_css_boxshadow(&self, px: u32) -> String {
+        let background = self.background.to_hex();
+        let mut shadows: Vec<String> = self
+            .painted_cells()
+            .map(|(row, col)| {
+                let (x, y) = (u32::from(col) * px, u32::from(row) * px);
+                format!("{}px {}px {}", x, y, self.cell_color(row, col).to_hex())
+            })
+            .collect();
+        let half = u32::from(NUM_SQUARES / 2) * px;
+        shadows.push(format!("{0}px {0}px 0 {0}px {1}", half, background));
+        format!(
+            "width:{}px;height:{}px;background:{};box-shadow:{};",
+            px,
+            px,
+            background,
+            shadows.join(",")
+        )
+    }
+
+    // an svg document of the image size with one rect per painted cell; like
+    // to_ppm, cells are plain squares, so shape, layout, jitter, opacity,
+    // retro style and shadow are not drawn
+    #[must_use]
+    pub fn to_svg(&self) -> String {
+        self.svg_string(false)
+    }
+
+    // to_svg with each cell rect carrying the class cell-r{row}-c{col}, for
+    // styling or animating single cells, while its color stays inline
+    #[must_use]
+    pub fn svg_with_classes(&self) -> String {
+        self.svg_string(true)
+    }
+
+    // stream the document of to_svg into w, rect by rect
+    pub fn write_svg<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.write_svg_with(w, false)
+    }
+
+    fn svg_string(&self, classes: bool) -> String {
+        let mut bytes = Vec::new();
+        // writing into a vec can't fail, and the document is all ascii
+        self.write_svg_with(&mut bytes, classes)
+            .expect("writing to a vec never fails");
+        String::from_utf8(bytes).expect("svg is ascii")
+    }
+
+    fn write_svg_with<W: Write>(&self, w: &mut W, classes: bool) -> io::Result<()> {
+        let (width, height) = self.dimensions();
+        let offset = self.offset().expect(FITS);
+        write!(
+            w,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">",
+            width, height
+        )?;
+        let mut rect =
+            |x: u32, y: u32, size: (u32, u32), color: &color::RGB, class: Option<String>| {
+                let class = class.map_or(String::new(), |class| format!(" class=\"{}\"", class));
+                write!(
+                    w,
+                    "<rect{} x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+                    class,
+                    x,
+                    y,
+                    size.0,
+                    size.1,
+                    color.to_hex()
+                )
+            };
+        match &self.border {
+            Some((border, color)) => {
+                rect(0, 0, (width, height), color, None)?;
+                let inner = |len: u32| len.saturating_sub(2 * border);
+                let size = (inner(width), inner(height));
+                rect(*border, *border, size, &self.background, None)?;
+            }
+            None => rect(0, 0, (width, height), &self.background, None)?,
+        }
+        for (row, col) in self.painted_cells() {
+            let color = self.cell_color(row, col);
+            let x = offset + u32::from(col) * self.width;
+            let y = offset + u32::from(row) * self.height;
+            let class = classes.then(|| format!("cell-r{}-c{}", row, col));
+            rect(x, y, (self.width, self.height), color, class)?;
+        }
+        write!(w, "</svg>")
+    }
+
+    // the grid as ansi truecolor blocks for terminal previews, two spaces per
+    // cell with its color as the background, resetting at each line end
+    #[must_use]
+    pub fn to_ansi(&self) -> String {
+        let mut ansi = String::new();
+        for (row, cells) in self.grid().iter().enumerate() {
+            for (col, painted) in cells.iter().enumerate() {
+                let color = match painted {
+                    true => self.cell_color(row as u8, col as u8),
+                    false => &self.background,
+                };
+                let [r, g, b] = color.as_pixel().0;
+                ansi.push_str(&format!("\x1b[48;2;{};{};{}m  ", r, g, b));
+            }
+            ansi.push_str("\x1b[0m\n");
+        }
+        ansi
+    }
+
+    // the grid and colors as json, for clients rendering the identicon themselves:
+    // {"grid":[[bool,...],...],"foreground":[r,g,b],"background":[r,g,b]}
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        let rows: Vec<String> = self
+            .grid()
+            .iter()
+            .map(|row| {
+                let cells: Vec<&str> = row
+                    .iter()
+                    .map(|c| if *c { "true" } else { "false" })
+                    .collect();
+                format!("[{}]", cells.join(","))
+            })
+            .collect();
+        let rgb = |color: &color::RGB| {
+            let [r, g, b] = color.as_pixel().0;
+            format!("[{},{},{}]", r, g, b)
+        };
+        format!(
+            "{{\"grid\":[{}],\"foreground\":{},\"background\":{}}}",
+            rows.join(","),
+            rgb(&self.foreground),
+            rgb(&self.background)
+        )
+    }
+
+    // the grid as lines of braille characters, each packing 2x4 cells;
+    // the grid is padded with unpainted cells to fill the last characters
+    #[must_use]
+    pub fn to_braille(&self) -> String {
+        Self::braille(&self.grid())
+    }
+
+    fn braille(grid: &[[bool; GRID_SIZE]; GRID_SIZE]) -> String {
+        // dot bit of each (col, row) within a braille character
+        const DOTS: [[u32; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+        let cell = |row: usize, col: usize| row < GRID_SIZE && col < GRID_SIZE && grid[row][col];
+
+        let lines: Vec<String> = (0..GRID_SIZE.div_ceil(4))
+            .map(|line| {
+                (0..GRID_SIZE.div_ceil(2))
+                    .map(|glyph| {
+                        let mut bits = 0;
+                        for (dx, dots) in DOTS.iter().enumerate() {
+                            for (dy, dot) in dots.iter().enumerate() {
+                                if cell(4 * line + dy, 2 * glyph + dx) {
+                                    bits |= dot;
+                                }
+                            }
+                        }
+                        char::from_u32(0x2800 + bits).unwrap_or(' ')
+                    })
+                    .collect()
+            })
+            .collect();
+        lines.join("\n")
+    }
+
+    // the color of a painted cell: the accent in the center if set, else the
+    // color of the cell's ring if ring colors are set, else the foreground
+    fn cell_color(&self, row: u8, col: u8) -> &color::RGB {
+        let last = NUM_SQUARES - 1;
+        let center = NUM_SQUARES / 2;
+        let ring = row.min(col).min(last - row).min(last - col);
+        match (&self.accent, &self.ring_colors) {
+            (Some(accent), _) if row == center && col == center => accent,
+            (_, Some(rings)) if ring >= 2 => &rings[usize::from(ring - 2)],
+            _ => &self.foreground,
+        }
+    }
+
+    // whether the i-th paint cell is drawn, either by the hash or as accent
+    fn is_painted(&self, i: usize) -> bool {
+        self.paints[i] || (self.accent.is_some() && i == Self::center_index())
+    }
+
+    // call put with the image coordinates and color of every painted pixel
+    fn for_each_painted(&self, put: impl FnMut(u32, u32, &color::RGB)) {
+        self.for_each_painted_in(0..u32::MAX, put)
+    }
+
+    // for_each_painted restricted to the image rows in window
+    fn for_each_painted_in(&self, window: Range<u32>, mut put: impl FnMut(u32, u32, &color::RGB)) {
+        if self.layout == Layout::HexGrid {
+            return self.for_each_painted_hex(window, put);
+        }
+        let (grid_width, grid_height) = self.grid_dimensions().expect(FITS);
+        let offset = self.offset().expect(FITS);
+        let window = Self::grid_window(window, offset);
+        for i in (0..NUM_PAINTS).filter(|&i| self.is_painted(i)) {
+            let (row, col) = Self::cell_position(i, NUM_SQUARES);
+            let color = self.cell_color(row, col);
+            let dark = color.as_hsl().darken(RETRO_DARKEN).as_rgb();
+            let (dx, dy) = self.jitter_offset(i);
+            let (x0, y0) = (u32::from(col) * self.width, u32::from(row) * self.height);
+            let cols = Self::shift(x0, self.width, dx, grid_width);
+            let rows = Self::shift(y0, self.height, dy, grid_height);
+            let rows = rows.start.max(window.start)..rows.end.min(window.end);
+
+            for x in cols {
+                for y in rows.clone() {
+                    // position within the cell, which the shifted range never precedes
+                    let cell_x = (i64::from(x) - i64::from(x0) - dx) as u32;
+                    let cell_y = (i64::from(y) - i64::from(y0) - dy) as u32;
+                    if !self.shape.covers(cell_x, cell_y, self.width, self.height) {
+                        continue;
+                    }
+                    // the checkerboard keeps its orientation in mirrored cells,
+                    // so that the center cell stays a checkerboard too
+                    let shade = |cell_x: u32| {
+                        let checker = (2 * cell_x / self.width + 2 * cell_y / self.height) % 2;
+                        if self.retro && checker == 1 {
+                            &dark
+                        } else {
+                            color
+                        }
+                    };
+                    put(offset + x, offset + y, shade(cell_x));
+                    if self.mirror {
+                        let mirrored = self.width - 1 - cell_x;
+                        put(offset + grid_width - 1 - x, offset + y, shade(mirrored));
+                    }
+                }
+            }
+        }
+    }
+
+    // the hex lattice takes its states from the grid: a shifted cell straddles
+    // two grid columns and takes the state of the one nearer the center, so
+    // only the left half is looked up and then mirrored
+    fn for_each_painted_hex(&self, window: Range<u32>, mut put: impl FnMut(u32, u32, &color::RGB)) {
+        let grid = self.grid();
+        let (grid_width, _) = self.grid_dimensions().expect(FITS);
+        let offset = self.offset().expect(FITS);
+        let window = Self::grid_window(window, offset);
+        let center = usize::from(NUM_SQUARES / 2);
+        for (row, cells) in grid.iter().enumerate() {
+            let shifted = row % 2 == 1;
+            let num_left = if shifted { center } else { center + 1 };
+            for i in 0..num_left {
+                let col = if shifted { i + 1 } else { i };
+                if !cells[col] {
+                    continue;
+                }
+                let color = self.cell_color(row as u8, col as u8);
+                let x0 = i as u32 * self.width + if shifted { self.width / 2 } else { 0 };
+                let y0 = row as u32 * self.height;
+                let rows = y0.max(window.start)..(y0 + self.height).min(window.end);
+                for x in x0..x0 + self.width {
+                    for y in rows.clone() {
+                        if !TileShape::Hexagon.covers(x - x0, y - y0, self.width, self.height) {
+                            continue;
+                        }
+                        put(offset + x, offset + y, color);
+                        if self.mirror {
+                            put(offset + grid_width - 1 - x, offset + y, color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // the background at (x, y) with each channel moved by a hash-seeded amount
+    // within ±noise
+    fn noisy_background(&self, x: u32, y: u32) -> Rgb<u8> {
+        let mut seed = [0u8; 8];
+        seed.copy_from_slice(&self.hash[..8]);
+        let key = u64::from_le_bytes(seed) ^ (u64::from(y) << 32 | u64::from(x));
+        let offsets = rng::mix(key).to_le_bytes();
+        let span = 2 * i32::from(self.noise) + 1;
+        let mut pixel = self.background.as_pixel();
+        for (c, offset) in pixel.0.iter_mut().zip(offsets) {
+            let offset = i32::from(offset) % span - i32::from(self.noise);
+            *c = (i32::from(*c) + offset).clamp(0, 255) as u8;
+        }
+        pixel
+    }
+
+    // image rows in window as rows of the grid, which starts offset pixels down
+    fn grid_window(window: Range<u32>, offset: u32) -> Range<u32> {
+        window.start.saturating_sub(offset)..window.end.saturating_sub(offset)
+    }
+
+    // hash-derived (dx, dy) of the i-th paint cell, each within ±jitter
+    fn jitter_offset(&self, i: usize) -> (i64, i64) {
+        let span = 2 * u16::from(self.jitter) + 1;
+        let offset = |b: u8| i64::from(u16::from(b) % span) - i64::from(self.jitter);
+        let len = self.hash.len();
+        (
+            offset(self.hash[i % len]),
+            offset(self.hash[(i + len / 2) % len]),
+        )
+    }
+
+    // the pixel range [start, start + len) shifted by delta, clipped to [0, max)
+    fn shift(start: u32, len: u32, delta: i64, max: u32) -> Range<u32> {
+        let clip = |v: i64| v.clamp(0, i64::from(max)) as u32;
+        let start = i64::from(start) + delta;
+        clip(start)..clip(start + i64::from(len))
+    }
+
+    // index into paints of the middle cell of the center column
+    fn center_index() -> usize {
+        let num_cols = usize::from(NUM_SQUARES / 2);
+        let num_rows = usize::from(NUM_SQUARES - 2);
+        num_rows / 2 * num_cols + num_cols - 1
+    }
+
+    // (row, col) in the full grid of the i-th paint cell; paints are stored
+    // row by row, covering the columns left of and including the center one
+    fn cell_position(i: usize, num_squares: u8) -> (u8, u8) {
+        let num_cols = usize::from(num_squares / 2);
+        (1 + (i / num_cols) as u8, 1 + (i % num_cols) as u8)
+    }
+
+    // index into paints of the cell driven by the i-th nibble; nibbles fill
+    // the cells column by column, starting from the center column
+    fn nibble_index(i: usize, num_squares: u8) -> usize {
+        let num_cols = usize::from(num_squares / 2);
+        let num_rows = usize::from(num_squares - 2);
+        let col = num_cols - 1 - i / num_rows;
+        let row = i % num_rows;
+        row * num_cols + col
+    }
+
+    // lum: the (min, max) luminance range the luminance byte is mapped onto
+    // custom_hue: the hue to use instead of the one from the hash
+    fn compute_fg(
+        hash: &HashBytes,
+        direction: Direction,
+        (lum_min, lum_max): (u16, u16),
+        custom_hue: Option<f32>,
+    ) -> Result<color::RGB> {
+        let h1 = (u16::from(hash[12]) & 0x0f) << 8;
+        let h2 = u16::from(hash[13]);
+
+        let hue = h1 | h2; // max 12 bits
+        let sat = hash[14];
+        let lum = hash[15];
+
+        let hue = match custom_hue {
+            Some(hue) => hue.rem_euclid(f32::from(color::HUE_MAX)),
+            None => util::map(f32::from(hue), 0.0, 4095.0, 0.0, f32::from(color::HUE_MAX)),
+        };
+        let sat = Self::map_byte(sat, SAT_MIN, SAT_MAX, direction);
+        let lum = Self::map_byte(lum, lum_min, lum_max, direction);
+
+        Ok(color::HSL::new(hue, sat, lum)?.as_rgb())
+    }
+
+    // linearly map a hash byte onto [min, max] in the given direction
+    fn map_byte(byte: u8, min: u16, max: u16, direction: Direction) -> f32 {
+        let (dmin, dmax) = match direction {
+            Direction::Ascending => (f32::from(min), f32::from(max)),
+            Direction::Descending => (f32::from(max), f32::from(min)),
+        };
+        util::map(f32::from(byte), 0.0, 255.0, dmin, dmax)
+    }
+
+    fn paint(hash: &HashBytes, paints: &mut Paints, rule: impl Fn(u8) -> bool) {
+        let len = paints.len();
+        let nibbles = hash
+            .iter()
+            .flat_map(|b| [(b & 0xf0) >> 4, (b & 0x0f)])
+            .take(len);
+
+        for (i, nibble) in nibbles.enumerate() {
+            paints[Self::nibble_index(i, NUM_SQUARES)] = rule(nibble);
+        }
+    }
+
+    // the center column follows the parity of CENTER_BYTE; the nibble of any
+    // other cell must fall below a threshold that shrinks toward the edges
+    fn paint_center_weighted(hash: &HashBytes, paints: &mut Paints) {
+        let center = hash[CENTER_BYTE].is_multiple_of(2);
+        let num_cols = usize::from(NUM_SQUARES / 2);
+        let num_rows = usize::from(NUM_SQUARES - 2);
+        let nibbles = hash.iter().flat_map(|b| [(b & 0xf0) >> 4, (b & 0x0f)]);
+        for (i, nibble) in nibbles.take(NUM_PAINTS).enumerate() {
+            // columns away from the center, as nibbles fill from the center
+            let distance = i / num_rows;
+            let threshold = 16 * (num_cols - distance) / num_cols;
+            paints[Self::nibble_index(i, NUM_SQUARES)] = match distance {
+                0 => center,
+                _ => usize::from(nibble) < threshold,
+            };
+        }
+    }
+}
+
+// the paints as bits, paint i at bit i
+pub fn paints_to_u16(paints: &Paints) -> u16 {
+    paints
+        .iter()
+        .enumerate()
+        .fold(0, |bits, (i, paint)| bits | u16::from(*paint) << i)
+}
+
+// paints from the low NUM_PAINTS bits, paint i at bit i; higher bits are ignored
+pub fn paints_from_u16(bits: u16) -> Paints {
+    let mut paints: Paints = [false; NUM_PAINTS];
+    for (i, paint) in paints.iter_mut().enumerate() {
+        *paint = bits >> i & 1 == 1;
+    }
+    paints
+}
+
+// birthday estimate of the probability that among n identicons two look the
+// same, over every paint pattern times the foregrounds told apart by eye:
+// whole degrees of hue and whole percents of saturation and luminance
+pub fn collision_probability(n: usize) -> f64 {
+    let patterns = f64::from(1u32 << NUM_PAINTS);
+    let hues = f64::from(color::HUE_MAX);
+    let sats = f64::from(SAT_MAX - SAT_MIN + 1);
+    let lums = f64::from(LUM_MAX - LUM_MIN + 1);
+    let space = patterns * hues * sats * lums;
+    let n = n as f64;
+    let pairs = n * (n - 1.0) / 2.0;
+    -(-pairs / space).exp_m1()
+}
+
+// one color per name from count hues spaced evenly around the wheel starting at
+// base_hue; names take the hues in the order of their hashes, so the assignment
+// doesn't depend on the order names are given in
+pub fn palette_for(names: &[&str], base_hue: f32, count: usize) -> Result<Vec<color::RGB>> {
+    let count = count.max(1);
+    let step = f32::from(color::HUE_MAX) / count as f32;
+    let sat = f32::from(SAT_MIN + SAT_MAX) / 2.0;
+    let lum = f32::from(LUM_MIN + LUM_MAX) / 2.0;
+
+    let hashes: Vec<HashBytes> = names
+        .iter()
+        .map(|name| Md5::digest(name.as_bytes()).into())
+        .collect();
+    let mut order: Vec<usize> = (0..names.len()).collect();
+    order.sort_by_key(|&i| hashes[i]);
+
+    let mut palette = vec![color::RGB::new(0, 0, 0); names.len()];
+    for (rank, i) in order.into_iter().enumerate() {
+        let hue = (base_hue + step * (rank % count) as f32).rem_euclid(f32::from(color::HUE_MAX));
+        palette[i] = color::HSL::new(hue, sat, lum)?.as_rgb();
+    }
+    Ok(palette)
+}
+
+// nudges foreground hues apart until any two are at least min_hue_gap degrees
+// apart around the wheel, keeping saturation, luminance and the painted cells;
+// the gap is capped at an even split of the wheel when it can't be met
+pub fn disperse_colors(identicons: &mut [Identicon], min_hue_gap: f32) -> Result<()> {
+    let n = identicons.len();
+    if n < 2 {
+        return Ok(());
+    }
+    let wheel = f32::from(color::HUE_MAX);
+    let gap = (min_hue_gap + HUE_SLACK).min(wheel / n as f32);
+    let hsls: Vec<color::HSL> = identicons.iter().map(Identicon::foreground_hsl).collect();
+
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&a, &b| hsls[a].hue().total_cmp(&hsls[b].hue()));
+    // start right after the widest gap, so pushing hues forward rarely wraps
+    // around onto the first one
+    let gap_before = |k: usize| {
+        let prev = hsls[order[(k + n - 1) % n]].hue();
+        (hsls[order[k]].hue() - prev).rem_euclid(wheel)
+    };
+    let start = (0..n)
+        .max_by(|&a, &b| gap_before(a).total_cmp(&gap_before(b)))
+        .unwrap_or(0);
+
+    let mut hues: Vec<f32> = Vec::with_capacity(n);
+    for k in 0..n {
+        let turn = if start + k >= n { wheel } else { 0.0 };
+        let hue = hsls[order[(start + k) % n]].hue() + turn;
+        hues.push(match hues.last() {
+            Some(prev) => hue.max(prev + gap),
+            None => hue,
+        });
+    }
+    if hues[n - 1] - hues[0] > wheel - gap {
+        let first = hues[0];
+        for (k, hue) in hues.iter_mut().enumerate() {
+            *hue = first + k as f32 * wheel / n as f32;
+        }
+    }
+
+    for (k, hue) in hues.into_iter().enumerate() {
+        let i = order[(start + k) % n];
+        let hue = hue.rem_euclid(wheel);
+        if hue != hsls[i].hue() {
+            let hsl = color::HSL::new(hue, hsls[i].sat(), hsls[i].lum())?;
+            identicons[i].set_foreground(hsl.as_rgb());
+        }
+    }
+    Ok(())
+}
+
+// the grids of a and b overlaid as cells size pixels square: cells painted
+// in neither get match_color, in one of them only_a or only_b, in both both
+pub fn diff_image(
+    a: &Identicon,
+    b: &Identicon,
+    size: u32,
+    match_color: &color::RGB,
+    only_a: &color::RGB,
+    only_b: &color::RGB,
+    both: &color::RGB,
+) -> RgbImage {
+    let (grid_a, grid_b) = (a.grid(), b.grid());
+    let len = GRID_SIZE as u32 * size;
+    RgbImage::from_fn(len, len, |x, y| {
+        let (row, col) = ((y / size) as usize, (x / size) as usize);
+        let color = match (grid_a[row][col], grid_b[row][col]) {
+            (false, false) => match_color,
+            (true, false) => only_a,
+            (false, true) => only_b,
+            (true, true) => both,
+        };
+        color.as_pixel()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::io::{Cursor, Read};
+
+    use image::codecs::gif::GifDecoder;
+    use image::{imageops, AnimationDecoder, ImageOutputFormat, RgbImage};
+    use md5::{Digest, Md5};
+
+    use super::{
+        color, diff_image, disperse_colors, paints_from_u16, paints_to_u16, palette_for, Algorithm,
+        Direction, Error, FilterType, Glyph, HashBytes, Identicon, IdenticonBuilder, Layout,
+        OutputFormat, Style, TileShape, BLOB_LEN, CENTER_BYTE, DARK_BG_LUM_MIN, DEFAULT_BACKGROUND,
+        GRID_SIZE, LUM_MAX, NUM_PAINTS, NUM_SQUARES, SAT_MAX, SAT_MIN,
+    };
+
+    #[test]
+    fn unmirrored_right_half_is_background() {
+        let mut identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        identicon.set_mirror(false);
+        let img = identicon.image();
+        let first_right_col = u32::from(NUM_SQUARES / 2 + 1) * 10;
+        for x in first_right_col..img.width() {
+            for y in 0..img.height() {
+                assert_eq!(*img.get_pixel(x, y), DEFAULT_BACKGROUND.as_pixel());
+            }
+        }
+    }
+
+    #[test]
+    fn default_background() {
+        assert_eq!(DEFAULT_BACKGROUND, color::RGB::new(240, 240, 240));
+    }
+
+    #[test]
+    fn dimensions_match_image() {
+        let identicon = Identicon::new("21012146", 13, DEFAULT_BACKGROUND).unwrap();
+        assert_eq!(identicon.dimensions(), identicon.image().dimensions());
+        assert_eq!(identicon.dimensions(), (91, 91));
+    }
+
+    #[test]
+    fn overflowing_dimensions() {
+        let mut identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        assert!(matches!(
+            identicon.set_padding(4_000_000_000),
+            Err(Error::TooLarge)
+        ));
+        assert!(matches!(
+            identicon.set_border(u32::MAX / 2, color::RGB::new(0, 0, 0)),
+            Err(Error::TooLarge)
+        ));
+        assert!(matches!(
+            identicon.set_size_wh(u32::MAX / 7 + 1, 1),
+            Err(Error::TooLarge)
+        ));
+        // rejected values leave the identicon as it was
+        assert_eq!(identicon.dimensions(), (70, 70));
+        assert_eq!(identicon.border, None);
+        assert!(matches!(
+            Identicon::new("21012146", u32::MAX, DEFAULT_BACKGROUND),
+            Err(Error::TooLarge)
+        ));
+    }
+
+    #[test]
+    fn accent_center_cell() {
+        let accent = color::RGB::new(255, 0, 0);
+        let mut identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        identicon.set_accent(accent.clone());
+        let img = identicon.image();
+        let (width, height) = img.dimensions();
+        assert_eq!(*img.get_pixel(width / 2, height / 2), accent.as_pixel());
+
+        for (i, paint) in identicon.paints.iter().enumerate() {
+            if *paint && i != Identicon::center_index() {
+                let (row, col) = Identicon::cell_position(i, NUM_SQUARES);
+                let x = u32::from(col) * 10;
+                let y = u32::from(row) * 10;
+                assert_eq!(*img.get_pixel(x, y), identicon.foreground.as_pixel());
+            }
+        }
+    }
+
+    #[test]
+    fn padding_and_border() {
+        let border = color::RGB::new(0, 0, 0);
+        let mut identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        identicon.set_padding(5).unwrap();
+        identicon.set_border(2, border.clone()).unwrap();
+        let img = identicon.image();
+        assert_eq!(identicon.dimensions(), (84, 84));
+        assert_eq!(img.dimensions(), (84, 84));
+        assert_eq!(*img.get_pixel(0, 0), border.as_pixel());
+        assert_eq!(*img.get_pixel(83, 40), border.as_pixel());
+        assert_eq!(*img.get_pixel(2, 2), DEFAULT_BACKGROUND.as_pixel());
+        assert_eq!(*img.get_pixel(6, 40), DEFAULT_BACKGROUND.as_pixel());
+    }
+
+    fn check_layout(num_squares: u8) {
+        let n = usize::from(num_squares);
+        let num_paints = (n - 2) * (n / 2);
+
+        let mut indices: Vec<usize> = (0..num_paints)
+            .map(|i| Identicon::nibble_index(i, num_squares))
+            .collect();
+        indices.sort_unstable();
+        assert_eq!(indices, (0..num_paints).collect::<Vec<usize>>());
+
+        let mut grid = vec![vec![false; n]; n];
+        for i in 0..num_paints {
+            let (row, col) = Identicon::cell_position(i, num_squares);
+            let (row, col) = (usize::from(row), usize::from(col));
+            assert!(!grid[row][col]);
+            grid[row][col] = true;
+            grid[row][n - 1 - col] = true;
+        }
+        for (row, cells) in grid.iter().enumerate() {
+            for (col, cell) in cells.iter().enumerate() {
+                let margin = row == 0 || col == 0 || row == n - 1 || col == n - 1;
+                assert_eq!(*cell, !margin);
+                assert_eq!(*cell, cells[n - 1 - col]);
+            }
+        }
+    }
+
+    #[test]
+    fn layout_5() {
+        check_layout(5);
+    }
+
+    #[test]
+    fn layout_7() {
+        check_layout(7);
+    }
+
+    #[test]
+    fn layout_9() {
+        check_layout(9);
+    }
+
+    #[test]
+    fn collisions() {
+        let a = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let mut b = Identicon::new("hubot", 20, color::RGB::new(0, 0, 0)).unwrap();
+        assert!(!a.collides_with(&b));
+
+        b.paints = a.paints;
+        b.foreground = a.foreground.clone();
+        assert!(a.collides_with(&b));
+        assert!(b.collides_with(&a));
+
+        b.foreground = color::RGB::new(1, 2, 3);
+        assert!(!a.collides_with(&b));
+    }
+
+    #[test]
+    fn png_metadata() {
+        let identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let bytes = identicon.png_with_metadata("Source", "21012146").unwrap();
+
+        let reader = png::Decoder::new(bytes.as_slice()).read_info().unwrap();
+        let text = &reader.info().uncompressed_latin1_text;
+        assert_eq!(text.len(), 1);
+        assert_eq!(text[0].keyword, "Source");
+        assert_eq!(text[0].text, "21012146");
+
+        let decoded = image::load_from_memory(&bytes).unwrap().to_rgb8();
+        assert_eq!(decoded, identicon.image());
+    }
+
+    #[test]
+    fn parse_format() {
+        assert_eq!("png".parse::<OutputFormat>().unwrap(), OutputFormat::Png);
+        assert_eq!("PNG".parse::<OutputFormat>().unwrap(), OutputFormat::Png);
+        assert_eq!("jpeg".parse::<OutputFormat>().unwrap(), OutputFormat::Jpeg);
+        assert_eq!("JPG".parse::<OutputFormat>().unwrap(), OutputFormat::Jpeg);
+        assert_eq!("jpg".parse::<OutputFormat>().unwrap(), OutputFormat::Jpeg);
+        assert_eq!("Gif".parse::<OutputFormat>().unwrap(), OutputFormat::Gif);
+        assert!(matches!(
+            "bmpx".parse::<OutputFormat>(),
+            Err(Error::UnknownFormat { val }) if val == "bmpx"
+        ));
+    }
+
+    #[test]
+    fn jitter() {
+        let plain = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let mut identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        identicon.set_jitter(0);
+        assert_eq!(identicon.image(), plain.image());
+
+        identicon.set_jitter(3);
+        let img = identicon.image();
+        assert_ne!(img, plain.image());
+        for (x, y, pixel) in img.enumerate_pixels() {
+            assert_eq!(pixel, img.get_pixel(img.width() - 1 - x, y));
+        }
+    }
+
+    #[test]
+    fn encode_error_names_format() {
+        let identicon = Identicon::new("21012146", 0, DEFAULT_BACKGROUND).unwrap();
+        match identicon.encode(OutputFormat::Png) {
+            Err(Error::Encode { format, .. }) => assert_eq!(format, "png"),
+            other => panic!("expect encode error but found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rng_is_reproducible() {
+        let a = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let b = Identicon::new("21012146", 20, DEFAULT_BACKGROUND).unwrap();
+        let c = Identicon::new("hubot", 10, DEFAULT_BACKGROUND).unwrap();
+        let first = |identicon: &Identicon| identicon.rng().take(8).collect::<Vec<u8>>();
+        assert_eq!(first(&a), first(&b));
+        assert_ne!(first(&a), first(&c));
+    }
+
+    #[test]
+    fn rectangular_squares() {
+        let mut identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        identicon.set_size_wh(20, 10).unwrap();
+        let img = identicon.image();
+        assert_eq!(img.dimensions(), (140, 70));
+        assert_eq!(identicon.dimensions(), (140, 70));
+        for (x, y, pixel) in img.enumerate_pixels() {
+            assert_eq!(pixel, img.get_pixel(img.width() - 1 - x, y));
+        }
+    }
+
+    #[test]
+    fn mapping_direction() {
+        for byte in [0, 37, 128, 255] {
+            let asc = Identicon::map_byte(byte, SAT_MIN, SAT_MAX, Direction::Ascending);
+            let desc = Identicon::map_byte(byte, SAT_MIN, SAT_MAX, Direction::Descending);
+            assert!((asc + desc - f32::from(SAT_MIN + SAT_MAX)).abs() < 1e-4);
+        }
+
+        let default = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let descending = IdenticonBuilder::new("21012146", 10, DEFAULT_BACKGROUND)
+            .direction(Direction::Descending)
+            .build()
+            .unwrap();
+        let ascending = IdenticonBuilder::new("21012146", 10, DEFAULT_BACKGROUND)
+            .direction(Direction::Ascending)
+            .build()
+            .unwrap();
+        assert_eq!(default.foreground, descending.foreground);
+        assert_ne!(default.foreground, ascending.foreground);
+    }
+
+    #[test]
+    fn render_to_vec_mime_types() {
+        let identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        for (format, mime) in [
+            (OutputFormat::Png, "image/png"),
+            (OutputFormat::Jpeg, "image/jpeg"),
+            (OutputFormat::Gif, "image/gif"),
+        ] {
+            let (bytes, mime_type) = identicon.render_to_vec(format).unwrap();
+            assert_eq!(mime_type, mime);
+            let img = image::load_from_memory(&bytes).unwrap();
+            assert_eq!((img.width(), img.height()), identicon.dimensions());
+        }
+    }
+
+    #[test]
+    fn mono() {
+        let mut identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        identicon.set_accent(color::RGB::new(255, 0, 0));
+        let mono = identicon.to_mono();
+        let img = identicon.image();
+        assert_eq!(mono.dimensions(), img.dimensions());
+        for (x, y, pixel) in mono.enumerate_pixels() {
+            let painted = *img.get_pixel(x, y) != DEFAULT_BACKGROUND.as_pixel();
+            assert_eq!(pixel.0[0], if painted { 0 } else { 255 });
+        }
+    }
+
+    #[test]
+    fn painted_cells_match_image() {
+        let identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let cells: HashSet<(u8, u8)> = identicon.painted_cells().collect();
+        assert_eq!(cells.len(), identicon.painted_cells().count());
+
+        let img = identicon.image();
+        for row in 0..NUM_SQUARES {
+            for col in 0..NUM_SQUARES {
+                let mirrored = (row, NUM_SQUARES - 1 - col);
+                assert_eq!(cells.contains(&(row, col)), cells.contains(&mirrored));
+                let pixel = img.get_pixel(u32::from(col) * 10 + 5, u32::from(row) * 10 + 5);
+                let painted = *pixel == identicon.foreground.as_pixel();
+                assert_eq!(cells.contains(&(row, col)), painted);
+            }
+        }
+    }
+
+    #[test]
+    fn foreground_override() {
+        let foreground = color::RGB::new(10, 20, 30);
+        let mut identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        identicon.set_foreground(foreground.clone());
+        let (row, col) = identicon.painted_cells().next().unwrap();
+        let pixel = identicon.image()[(u32::from(col) * 10, u32::from(row) * 10)];
+        assert_eq!(pixel, foreground.as_pixel());
+    }
+
+    #[test]
+    fn opacity_blends_with_background() {
+        let background = color::RGB::new(0, 100, 255);
+        let mut identicon = Identicon::new("21012146", 10, background.clone()).unwrap();
+        identicon.set_opacity(0.5);
+        let fg = identicon.foreground.as_pixel().0;
+        let bg = background.as_pixel().0;
+        let midpoint: Vec<u8> = (0..3)
+            .map(|i| ((f32::from(fg[i]) + f32::from(bg[i])) / 2.0).round() as u8)
+            .collect();
+
+        let img = identicon.image();
+        for (row, col) in identicon.painted_cells() {
+            let pixel = img[(u32::from(col) * 10, u32::from(row) * 10)];
+            assert_eq!(pixel.0.to_vec(), midpoint);
+        }
+    }
+
+    #[test]
+    fn salted() {
+        let plain = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let a = Identicon::new_salted("21012146", "a", 10, DEFAULT_BACKGROUND).unwrap();
+        let b = Identicon::new_salted("21012146", "b", 10, DEFAULT_BACKGROUND).unwrap();
+        let again = Identicon::new_salted("21012146", "a", 10, DEFAULT_BACKGROUND).unwrap();
+        assert_ne!(a.hash, plain.hash);
+        assert_ne!(a.hash, b.hash);
+        assert_eq!(a.hash, again.hash);
+    }
+
+    #[test]
+    fn json() {
+        let background = color::RGB::new(1, 2, 3);
+        let mut identicon = Identicon::new("21012146", 10, background).unwrap();
+        identicon.set_foreground(color::RGB::new(4, 5, 6));
+        let json = identicon.to_json();
+
+        let grid = json
+            .strip_prefix("{\"grid\":[[")
+            .and_then(|s| s.strip_suffix("]],\"foreground\":[4,5,6],\"background\":[1,2,3]}"))
+            .unwrap();
+        let rows: Vec<Vec<bool>> = grid
+            .split("],[")
+            .map(|row| row.split(',').map(|c| c.parse::<bool>().unwrap()).collect())
+            .collect();
+        assert_eq!(rows.len(), GRID_SIZE);
+        for (row, expected) in rows.iter().zip(identicon.grid()) {
+            assert_eq!(row.as_slice(), expected.as_slice());
+        }
+    }
+
+    #[test]
+    fn rounded_square_tiles() {
+        let mut identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        identicon.set_shape(TileShape::RoundedSquare { radius: 3 });
+        let img = identicon.image();
+        let fg = identicon.foreground.as_pixel();
+        let bg = DEFAULT_BACKGROUND.as_pixel();
+        for (row, col) in identicon.painted_cells() {
+            let (x, y) = (u32::from(col) * 10, u32::from(row) * 10);
+            for (cx, cy) in [(0, 0), (9, 0), (0, 9), (9, 9)] {
+                assert_eq!(img[(x + cx, y + cy)], bg);
+            }
+            for (ex, ey) in [(5, 0), (0, 5), (9, 5), (5, 9), (5, 5)] {
+                assert_eq!(img[(x + ex, y + ey)], fg);
+            }
+        }
+    }
+
+    #[test]
+    fn render_onto_canvas() {
+        let identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let texture = |x: u32, y: u32| image::Rgb([(x % 256) as u8, (y % 256) as u8, 7]);
+        let mut canvas = RgbImage::from_fn(100, 90, texture);
+        identicon.render_onto(&mut canvas, 20, 15);
+
+        let img = identicon.image();
+        for (x, y, pixel) in canvas.enumerate_pixels() {
+            let inside = (20..90).contains(&x) && (15..85).contains(&y);
+            if inside && img[(x - 20, y - 15)] == identicon.foreground.as_pixel() {
+                assert_eq!(*pixel, identicon.foreground.as_pixel());
+            } else {
+                assert_eq!(*pixel, texture(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn render_onto_translucent() {
+        let mut identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        identicon.set_foreground(color::RGB::new(0, 0, 0));
+        identicon.set_opacity(0.5);
+        let white = image::Rgb([255, 255, 255]);
+        let mut canvas = RgbImage::from_pixel(70, 70, white);
+        identicon.render_onto(&mut canvas, 0, 0);
+
+        // every painted pixel, the mirrored center column included, is
+        // blended once
+        let grid = identicon.grid();
+        assert!(grid[1..6].iter().any(|row| row[3]));
+        for (x, y, pixel) in canvas.enumerate_pixels() {
+            let painted = grid[(y / 10) as usize][(x / 10) as usize];
+            let expected = match painted {
+                true => image::Rgb([128, 128, 128]),
+                false => white,
+            };
+            assert_eq!(*pixel, expected, "({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn hex_grid() {
+        let hex = |name: &str| {
+            let mut identicon = Identicon::new(name, 10, DEFAULT_BACKGROUND).unwrap();
+            identicon.set_layout(Layout::HexGrid);
+            identicon.image()
+        };
+        let img = hex("21012146");
+        assert_eq!(img, hex("21012146"));
+        assert_ne!(
+            img,
+            Identicon::new("21012146", 10, DEFAULT_BACKGROUND)
+                .unwrap()
+                .image()
+        );
+        assert!(img.pixels().any(|p| *p != DEFAULT_BACKGROUND.as_pixel()));
+        for (x, y, pixel) in img.enumerate_pixels() {
+            assert_eq!(pixel, img.get_pixel(img.width() - 1 - x, y));
+        }
+    }
+
+    #[test]
+    fn error_predicates() {
+        let color = Error::from(color::HSL::new(-1.0, 0.0, 0.0).err().unwrap());
+        assert!(color.is_color());
+        assert!(!color.is_encoding());
+
+        let identicon = Identicon::new("21012146", 0, DEFAULT_BACKGROUND).unwrap();
+        let encoding = identicon.encode(OutputFormat::Png).unwrap_err();
+        assert!(encoding.is_encoding());
+        assert!(!encoding.is_color());
+
+        let unknown = "xyz".parse::<OutputFormat>().unwrap_err();
+        assert!(!unknown.is_color() && !unknown.is_encoding());
+    }
+
+    #[test]
+    fn render_into() {
+        let mut identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        identicon.set_padding(2).unwrap();
+        identicon.set_border(3, color::RGB::new(0, 0, 0)).unwrap();
+        identicon.set_center_glyph(true);
+        let (width, height) = identicon.dimensions();
+        let stride = width as usize * 3 + 5;
+        let mut buf = vec![0u8; stride * height as usize];
+        identicon
+            .render_into(&mut buf, stride, width, height)
+            .unwrap();
+        // drawn row by row, leaving the cache empty
+        assert!(identicon.rendered.0.get().is_none());
+        let img = identicon.image();
+        for (y, row) in img.as_raw().chunks_exact(width as usize * 3).enumerate() {
+            let start = y * stride;
+            assert_eq!(&buf[start..start + row.len()], row);
+            // the padding at the end of each row is untouched
+            assert!(buf[start + row.len()..start + stride]
+                .iter()
+                .all(|&b| b == 0));
+        }
+
+        let err = identicon.render_into(&mut buf[..stride * 10], stride, width, height);
+        assert!(matches!(err, Err(Error::InvalidBuffer { .. })));
+        let err = identicon.render_into(&mut buf, stride, width - 1, height);
+        assert!(matches!(err, Err(Error::InvalidBuffer { .. })));
+        let err = identicon.render_into(&mut buf, 3, width, height);
+        assert!(matches!(err, Err(Error::InvalidBuffer { .. })));
+        let err = identicon.render_into(&mut buf, usize::MAX, width, height);
+        assert!(matches!(err, Err(Error::InvalidBuffer { .. })));
+    }
+
+    #[test]
+    fn render_create_dirs() {
+        let identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let root = std::env::temp_dir().join(format!("identicon-dirs-{}", std::process::id()));
+        let path = root.join("a").join("b").join("avatar.png");
+        let err = identicon.render(&path).unwrap_err();
+        assert!(
+            matches!(err, Error::Io { .. }) && !err.is_encoding(),
+            "{}",
+            err
+        );
+        let mut transparent = identicon.clone();
+        transparent.set_transparent(true);
+        assert!(matches!(transparent.render(&path), Err(Error::Io { .. })));
+        identicon.render_or_default(&path);
+        assert!(!path.exists());
+        identicon.render_create_dirs(&path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(
+            image::guess_format(&bytes).unwrap(),
+            image::ImageFormat::Png
+        );
+
+        // a file in the way of a directory
+        let file = std::env::temp_dir().join(format!("identicon-file-{}", std::process::id()));
+        std::fs::write(&file, b"").unwrap();
+        let err = identicon
+            .render_create_dirs(&file.join("avatar.png"))
+            .unwrap_err();
+        std::fs::remove_file(&file).unwrap();
+        assert!(matches!(err, Error::Io { .. }), "{}", err);
+    }
+
+    #[test]
+    fn render_as_format() {
+        let identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let path = std::env::temp_dir().join(format!("identicon-render-as-{}", std::process::id()));
+        identicon.render_as(&path, OutputFormat::Gif).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            image::guess_format(&bytes).unwrap(),
+            image::ImageFormat::Gif
+        );
+        assert_eq!(OutputFormat::Gif.extension(), "gif");
+    }
+
+    #[test]
+    fn ensure_contrast() {
+        for background in [DEFAULT_BACKGROUND, color::RGB::new(20, 20, 30)] {
+            let mut identicon = Identicon::new("21012146", 10, background.clone()).unwrap();
+            identicon.set_foreground(background.blend(&color::RGB::new(128, 128, 255), 0.8));
+            assert!(identicon.foreground.contrast_ratio(&background) < 4.5);
+            identicon.ensure_contrast(4.5).unwrap();
+            assert!(identicon.foreground.contrast_ratio(&background) >= 4.5);
+        }
+    }
+
+    #[test]
+    fn from_u64_byte_order() {
+        let be = Identicon::from_u64_be(1, 10, DEFAULT_BACKGROUND).unwrap();
+        let le = Identicon::from_u64_le(1, 10, DEFAULT_BACKGROUND).unwrap();
+        assert_ne!(be.hash, le.hash);
+        assert_eq!(
+            be.hash,
+            Identicon::from_u64(1, 10, DEFAULT_BACKGROUND).unwrap().hash
+        );
+
+        let mut hasher = Md5::new();
+        hasher.update([0, 0, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(be.hash, <HashBytes>::from(hasher.finalize()));
+        let mut hasher = Md5::new();
+        hasher.update([1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(le.hash, <HashBytes>::from(hasher.finalize()));
+    }
+
+    #[test]
+    fn retro_checkerboard() {
+        let mut identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        identicon.set_retro(true);
+        let img = identicon.image();
+        let fg = identicon.foreground.as_pixel();
+        for (row, col) in identicon.painted_cells() {
+            let (x, y) = (u32::from(col) * 10, u32::from(row) * 10);
+            let quadrant = |qx: u32, qy: u32| img[(x + qx * 5 + 2, y + qy * 5 + 2)];
+            assert_eq!(quadrant(0, 0), fg);
+            assert_eq!(quadrant(1, 1), fg);
+            assert_ne!(quadrant(1, 0), fg);
+            assert_eq!(quadrant(1, 0), quadrant(0, 1));
+            assert_ne!(quadrant(1, 0), DEFAULT_BACKGROUND.as_pixel());
+        }
+    }
+
+    #[test]
+    fn braille() {
+        let identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let braille = identicon.to_braille();
+        let lines: Vec<&str> = braille.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.chars().count() == 4));
+
+        let full = Identicon::braille(&[[true; GRID_SIZE]; GRID_SIZE]);
+        let lines: Vec<Vec<char>> = full.lines().map(|line| line.chars().collect()).collect();
+        assert_eq!(lines[0][..3], ['\u{28ff}'; 3]);
+        // the padding column and row stay empty
+        assert_eq!(lines[0][3], '\u{2847}');
+        assert_eq!(lines[1][0], '\u{283f}');
+        assert_eq!(
+            Identicon::braille(&[[false; GRID_SIZE]; GRID_SIZE]),
+            "\u{2800}".repeat(4) + "\n" + &"\u{2800}".repeat(4)
+        );
+    }
+
+    #[test]
+    fn transparent_gif() {
+        let mut identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        identicon.set_transparent(true);
+        let (bytes, _) = identicon.render_to_vec(OutputFormat::Gif).unwrap();
+        let decoded = image::load_from_memory(&bytes).unwrap().to_rgba8();
+        assert_eq!(decoded[(0, 0)].0[3], 0);
+        let (row, col) = identicon.painted_cells().next().unwrap();
+        assert_eq!(
+            decoded[(u32::from(col) * 10, u32::from(row) * 10)].0[3],
+            255
+        );
+
+        let (bytes, _) = identicon.render_to_vec(OutputFormat::Png).unwrap();
+        let decoded = image::load_from_memory(&bytes).unwrap();
+        assert!(decoded.color().has_alpha());
+        assert_eq!(decoded.to_rgba8()[(0, 0)].0[3], 0);
+        assert!(matches!(
+            identicon.render_to_vec(OutputFormat::Jpeg),
+            Err(Error::NoAlpha { format: "jpeg" })
+        ));
+
+        identicon.set_foreground(DEFAULT_BACKGROUND);
+        assert!(matches!(
+            identicon.render_to_vec(OutputFormat::Gif),
+            Err(Error::TransparentForeground)
+        ));
+    }
+
+    #[test]
+    fn transparent_after_noisy_render() {
+        let mut identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        identicon.set_noisy_background(20);
+        let _ = identicon.rendered();
+        identicon.set_transparent(true);
+        let (bytes, _) = identicon.render_to_vec(OutputFormat::Png).unwrap();
+        let decoded = image::load_from_memory(&bytes).unwrap().to_rgba8();
+
+        let mut fresh = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        fresh.set_noisy_background(20);
+        fresh.set_transparent(true);
+        let (bytes, _) = fresh.render_to_vec(OutputFormat::Png).unwrap();
+        let expected = image::load_from_memory(&bytes).unwrap().to_rgba8();
+        assert_eq!(decoded[(0, 0)].0[3], 0);
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn injected_hash_bytes() {
+        let mut hash = [0u8; 16];
+        hash[..8].copy_from_slice(&[0x01; 8]);
+        let identicon = IdenticonBuilder::new("ignored", 10, DEFAULT_BACKGROUND)
+            .with_hash_bytes(hash)
+            .build()
+            .unwrap();
+
+        // even nibbles paint; alternating 0, 1 nibbles land on alternating cells
+        let expected: Vec<bool> = (0..NUM_PAINTS).map(|i| i % 2 == 0).collect();
+        assert_eq!(identicon.paints.to_vec(), expected);
+        // zero color bytes give hue 0 with the maximum saturation and luminance
+        let foreground = color::HSL::new(0.0, f32::from(SAT_MAX), f32::from(LUM_MAX))
+            .unwrap()
+            .as_rgb();
+        assert_eq!(identicon.foreground, foreground);
+    }
+
+    #[test]
+    fn css_boxshadow() {
+        let identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let css = identicon.to_css_boxshadow(4);
+        assert!(css.starts_with("width:4px;height:4px;background:#f0f0f0;box-shadow:"));
+        let shadows: Vec<&str> = css
+            .split("box-shadow:")
+            .nth(1)
+            .unwrap()
+            .split(',')
+            .collect();
+        // one per painted cell, then the background spread over the 28px grid
+        assert_eq!(shadows.len(), identicon.painted_cells().count() + 1);
+        assert!(shadows[0].ends_with(&identicon.foreground.to_hex()));
+        assert_eq!(*shadows.last().unwrap(), "12px 12px 0 12px #f0f0f0;");
+
+        // cells take the accent and ring colors
+        let mut identicon = identicon;
+        identicon.set_accent(color::RGB::new(255, 0, 0));
+        identicon.set_ring_colors(true).unwrap();
+        let css = identicon.to_css_boxshadow(4);
+        assert!(css.contains("12px 12px #ff0000"), "{}", css);
+        for (row, col) in identicon.painted_cells() {
+            let (x, y) = (u32::from(col) * 4, u32::from(row) * 4);
+            let color = identicon.cell_color(row, col).to_hex();
+            assert!(css.contains(&format!("{}px {}px {}", x, y, color)));
+        }
+    }
+
+    #[test]
+    fn write_svg() {
+        let mut identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        identicon.set_border(2, color::RGB::new(0, 0, 0)).unwrap();
+        let mut bytes = Vec::new();
+        identicon.write_svg(&mut bytes).unwrap();
+        assert_eq!(String::from_utf8(bytes).unwrap(), identicon.to_svg());
+    }
+
+    #[test]
+    fn average_color() {
+        let builder =
+            IdenticonBuilder::new("21012146", 10, DEFAULT_BACKGROUND).with_hash_bytes([0xff; 16]);
+        let blank = builder.build().unwrap();
+        assert_eq!(blank.average_color(), DEFAULT_BACKGROUND);
+
+        // every cell inside the outer ring painted: 25 of the 49 cells
+        let builder =
+            IdenticonBuilder::new("21012146", 10, DEFAULT_BACKGROUND).paint_rule(|_| true);
+        let mut full = builder.build().unwrap();
+        full.set_foreground(color::RGB::new(0, 0, 0));
+        let gray = ((240 * 24 + 49 / 2) / 49) as u8;
+        assert_eq!(full.average_color(), color::RGB::new(gray, gray, gray));
+
+        // matches the mean of the rendered pixels
+        let mut identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        identicon.set_border(3, color::RGB::new(0, 0, 255)).unwrap();
+        identicon.set_padding(2).unwrap();
+        let img = identicon.image();
+        let n = u64::from(img.width() * img.height());
+        let mean = |i: usize| {
+            let sum: u64 = img.pixels().map(|p| u64::from(p.0[i])).sum();
+            ((sum + n / 2) / n) as u8
+        };
+        let expected = color::RGB::new(mean(0), mean(1), mean(2));
+        assert_eq!(identicon.average_color(), expected);
+    }
+
+    #[test]
+    fn svg_classes() {
+        let identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let svg = identicon.svg_with_classes();
+        assert!(svg.starts_with("<svg ") && svg.ends_with("</svg>"));
+        assert_eq!(
+            svg.matches("class=\"cell-").count(),
+            identicon.painted_cells().count()
+        );
+        let grid = identicon.grid();
+        for (row, cells) in grid.iter().enumerate() {
+            for (col, painted) in cells.iter().enumerate() {
+                let class = format!("class=\"cell-r{}-c{}\"", row, col);
+                assert_eq!(svg.contains(&class), *painted, "{}", class);
+            }
+        }
+        assert!(!identicon.to_svg().contains("class="));
+    }
+
+    #[test]
+    fn palette() {
+        let names = ["alice", "bob", "carol", "dave"];
+        let palette = palette_for(&names, 30.0, names.len()).unwrap();
+        assert_eq!(palette.len(), names.len());
+
+        let mut hues: Vec<f32> = palette.iter().map(|c| c.as_hsl().hue()).collect();
+        hues.sort_by(f32::total_cmp);
+        for (i, hue) in hues.iter().enumerate() {
+            assert!((hue - (30.0 + 90.0 * i as f32)).abs() < 1.0, "{:?}", hues);
+        }
+
+        // the assignment follows the hashes, not the argument order
+        let reversed: Vec<&str> = names.iter().rev().copied().collect();
+        let mut expected = palette_for(&reversed, 30.0, names.len()).unwrap();
+        expected.reverse();
+        assert_eq!(palette, expected);
+    }
+
+    #[test]
+    fn rotate_and_flip() {
+        let identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        // the pattern is mirrored already
+        assert_eq!(identicon.flip_horizontal(), identicon.image());
+        assert_eq!(identicon.rotate(4), identicon.image());
+
+        let flipped = imageops::flip_horizontal(&identicon.flip_vertical());
+        assert_eq!(identicon.rotate(2), flipped);
+        assert_ne!(identicon.rotate(1), identicon.image());
+    }
+
+    #[test]
+    fn verify() {
+        let identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let bytes = identicon.bytes(OutputFormat::Png).unwrap();
+        assert!(Identicon::verify("21012146", 10, DEFAULT_BACKGROUND, &bytes).unwrap());
+        assert!(!Identicon::verify("21012147", 10, DEFAULT_BACKGROUND, &bytes).unwrap());
+
+        let mut tampered = identicon.image();
+        tampered.put_pixel(0, 0, color::RGB::new(0, 0, 0).as_pixel());
+        let mut bytes = Vec::new();
+        tampered
+            .write_to(&mut Cursor::new(&mut bytes), ImageOutputFormat::Png)
+            .unwrap();
+        assert!(!Identicon::verify("21012146", 10, DEFAULT_BACKGROUND, &bytes).unwrap());
+
+        assert!(matches!(
+            Identicon::verify("21012146", 10, DEFAULT_BACKGROUND, b"not a png"),
+            Err(Error::Decode { .. })
+        ));
+    }
+
+    #[test]
+    fn disperse() {
+        let mut identicons: Vec<Identicon> = (0..6)
+            .map(|i| {
+                let mut identicon = Identicon::new(&i.to_string(), 10, DEFAULT_BACKGROUND).unwrap();
+                let hue = 10.0 + 5.0 * i as f32;
+                identicon.set_foreground(color::HSL::new(hue, 60.0, 60.0).unwrap().as_rgb());
+                identicon
+            })
+            .collect();
+        let grids: Vec<_> = identicons.iter().map(Identicon::grid).collect();
+
+        disperse_colors(&mut identicons, 40.0).unwrap();
+        for (i, a) in identicons.iter().enumerate() {
+            for b in &identicons[i + 1..] {
+                let d = (a.foreground_hsl().hue() - b.foreground_hsl().hue()).rem_euclid(360.0);
+                assert!(d.min(360.0 - d) >= 40.0, "{}", d);
+            }
+        }
+        let after: Vec<_> = identicons.iter().map(Identicon::grid).collect();
+        assert_eq!(grids, after);
+    }
+
+    #[test]
+    fn png_indexed() {
+        let mut identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        for accent in [None, Some(color::RGB::new(255, 0, 0))] {
+            if let Some(accent) = accent {
+                identicon.set_accent(accent);
+                identicon.set_retro(true);
+            }
+            let bytes = identicon.png_indexed().unwrap();
+            let decoded = image::load_from_memory(&bytes).unwrap().to_rgb8();
+            assert_eq!(decoded, identicon.image());
+            assert!(bytes.len() < identicon.bytes(OutputFormat::Png).unwrap().len());
+        }
+    }
+
+    #[test]
+    fn thumbnail_filters() {
+        let identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        // 70px down to 32px is no whole ratio, so the filters sample differently
+        let nearest = identicon.thumbnail(32, FilterType::Nearest);
+        let lanczos = identicon.thumbnail(32, FilterType::Lanczos3);
+        assert_eq!(nearest.dimensions(), (32, 32));
+        assert_eq!(lanczos.dimensions(), (32, 32));
+        assert_ne!(nearest, lanczos);
+
+        assert_eq!(
+            identicon.thumbnail(0, FilterType::Nearest).dimensions(),
+            (0, 0)
+        );
+
+        let empty = Identicon::new("21012146", 0, DEFAULT_BACKGROUND).unwrap();
+        let thumbnail = empty.thumbnail(32, FilterType::Nearest);
+        assert_eq!(
+            thumbnail,
+            RgbImage::from_pixel(32, 32, DEFAULT_BACKGROUND.as_pixel())
+        );
+    }
+
+    #[test]
+    fn center_glyph() {
+        let glyph = |name: &str| {
+            let mut identicon = Identicon::new(name, 10, DEFAULT_BACKGROUND).unwrap();
+            assert_eq!(identicon.center_glyph(), None);
+            identicon.set_center_glyph(true);
+            identicon.center_glyph().unwrap()
+        };
+        assert_eq!(glyph("21012146"), glyph("21012146"));
+        let glyphs: HashSet<Glyph> = (0..20).map(|n| glyph(&n.to_string())).collect();
+        assert!(glyphs.len() > 1);
+
+        let mut identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let plain = identicon.image();
+        identicon.set_center_glyph(true);
+        let img = identicon.image();
+        assert_ne!(img, plain);
+
+        // changes only the center cell, to the color it is not
+        let under = plain.get_pixel(35, 35);
+        for (x, y, p) in img.enumerate_pixels() {
+            if p != plain.get_pixel(x, y) {
+                assert!((30..40).contains(&x) && (30..40).contains(&y));
+                assert_ne!(p, under);
+            }
+        }
+    }
+
+    #[test]
+    fn blob_round_trip() {
+        let builder =
+            IdenticonBuilder::new("21012146", 10, DEFAULT_BACKGROUND).center_weighted(true);
+        let mut identicon = builder.build().unwrap();
+        identicon.set_size_wh(8, 12).unwrap();
+        identicon.set_retro(true);
+        identicon.set_center_glyph(true);
+        let blob = identicon.to_bytes();
+        assert_eq!(blob.len(), BLOB_LEN);
+        let restored = Identicon::from_bytes_blob(&blob).unwrap();
+        assert_eq!(restored.image(), identicon.image());
+        assert_eq!(restored.to_bytes(), blob);
+
+        let mut bad = blob.clone();
+        bad[0] = 0;
+        assert!(matches!(
+            Identicon::from_bytes_blob(&bad),
+            Err(Error::InvalidBlob { .. })
+        ));
+        assert!(Identicon::from_bytes_blob(&blob[..BLOB_LEN - 1]).is_err());
+    }
+
+    #[test]
+    fn diff() {
+        let a = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let b = Identicon::new("alice", 10, DEFAULT_BACKGROUND).unwrap();
+        let colors = [(0, 0, 0), (255, 0, 0), (0, 0, 255), (0, 255, 0)]
+            .map(|(r, g, b)| color::RGB::new(r, g, b));
+        let [neither, only_a, only_b, both] = &colors;
+        let img = diff_image(&a, &b, 4, neither, only_a, only_b, both);
+        assert_eq!(img.dimensions(), (28, 28));
+
+        let (grid_a, grid_b) = (a.grid(), b.grid());
+        let mut seen = HashSet::new();
+        for row in 0..GRID_SIZE {
+            for col in 0..GRID_SIZE {
+                let expected = match (grid_a[row][col], grid_b[row][col]) {
+                    (false, false) => neither,
+                    (true, false) => only_a,
+                    (false, true) => only_b,
+                    (true, true) => both,
+                };
+                let pixel = img.get_pixel(col as u32 * 4 + 1, row as u32 * 4 + 2);
+                assert_eq!(*pixel, expected.as_pixel());
+                seen.insert(expected.to_hex());
+            }
+        }
+        assert!(seen.contains(&only_a.to_hex()) && seen.contains(&both.to_hex()));
+    }
+
+    #[test]
+    fn f32_buffer() {
+        let mut identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        identicon.set_size_wh(4, 6).unwrap();
+        let (buffer, width, height) = identicon.to_f32_buffer();
+        assert_eq!((width, height), identicon.dimensions());
+        assert_eq!(buffer.len(), (width * height * 3) as usize);
+        assert!(buffer.iter().all(|c| (0.0..=1.0).contains(c)));
+        // row-major: the pixel at (x, y) starts at (y * width + x) * 3
+        let img = identicon.image();
+        let (x, y) = (12, 20);
+        let i = ((y * width + x) * 3) as usize;
+        let pixel = img.get_pixel(x, y).0.map(|c| f32::from(c) / 255.0);
+        assert_eq!(buffer[i..i + 3], pixel);
+    }
+
+    #[test]
+    fn accessible() {
+        let mut schemes = HashSet::new();
+        for n in 0..10 {
+            let identicon = Identicon::accessible(&n.to_string(), 10).unwrap();
+            let ratio = identicon.foreground.contrast_ratio(&identicon.background);
+            assert!(ratio >= 7.0, "{}", ratio);
+            assert!(!identicon.is_blank());
+            schemes.insert(identicon.background.to_hex());
+            // every painted cell joins a painted neighbor, so none is a speck
+            let grid = identicon.grid();
+            for (row, col) in identicon.painted_cells() {
+                let (row, col) = (usize::from(row), usize::from(col));
+                assert!(
+                    grid[row - 1][col]
+                        || grid[row + 1][col]
+                        || grid[row][col - 1]
+                        || grid[row][col + 1],
+                    "{} has an isolated cell at {:?}",
+                    n,
+                    (row, col)
+                );
+            }
+        }
+        // both black on white and white on black occur
+        assert_eq!(schemes.len(), 2);
+    }
+
+    #[test]
+    fn ring_colors() {
+        let mut identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let plain = identicon.image();
+        identicon.set_ring_colors(true).unwrap();
+        let img = identicon.image();
+        let background = identicon.background.as_pixel();
+        let colors: HashSet<[u8; 3]> = img
+            .pixels()
+            .filter(|p| **p != background)
+            .map(|p| p.0)
+            .collect();
+        assert!(colors.len() >= 2, "{:?}", colors);
+        // the same cells are painted, just colored per ring
+        for (p, q) in img.pixels().zip(plain.pixels()) {
+            assert_eq!(*p == background, *q == background);
+        }
+
+        let mut again = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        again.set_ring_colors(true).unwrap();
+        assert_eq!(again.image(), img);
+        again.set_ring_colors(false).unwrap();
+        assert_eq!(again.image(), plain);
+
+        // the plain-square ppm colors its cells the same way
+        let ppm = identicon.to_ppm();
+        let header = b"P6\n70 70\n255\n";
+        assert_eq!(&ppm[header.len()..], img.as_raw().as_slice());
+    }
+
+    #[test]
+    fn tileable() {
+        let mut identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        identicon.set_mirror(false);
+        let (width, height) = identicon.dimensions();
+        let tiled = identicon.tileable(3, 2).unwrap();
+        assert_eq!(tiled.dimensions(), (3 * width, 2 * height));
+        for tx in 1..3 {
+            for y in 0..2 * height {
+                let edge = tx * width;
+                assert_eq!(tiled.get_pixel(edge - 1, y), tiled.get_pixel(edge, y));
+            }
+        }
+        for x in 0..3 * width {
+            assert_eq!(tiled.get_pixel(x, height - 1), tiled.get_pixel(x, height));
+        }
+        assert!(matches!(
+            identicon.tileable(u32::MAX, 1),
+            Err(Error::TooLarge)
+        ));
+        assert!(matches!(
+            identicon.tileable(1, u32::MAX),
+            Err(Error::TooLarge)
+        ));
+    }
+
+    #[test]
+    fn circular() {
+        let identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let img = identicon.circular();
+        assert_eq!(img.dimensions(), identicon.dimensions());
+        for (x, y) in [(0, 0), (69, 0), (0, 69), (69, 69)] {
+            assert_eq!(img.get_pixel(x, y)[3], 0);
+        }
+        assert_eq!(img.get_pixel(35, 35)[3], 255);
+        // partially covered pixels along the boundary
+        assert!(img.pixels().any(|p| p[3] > 0 && p[3] < 255));
+    }
+
+    #[test]
+    fn send_sync() {
+        // the memoized image must stay in a thread-safe cell
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Identicon>();
+        assert_send_sync::<color::RGB>();
+        assert_send_sync::<Error>();
+    }
+
+    #[test]
+    fn ico_multi() {
+        let identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let sizes = [16, 32, 48];
+        let bytes = identicon.ico_multi(&sizes).unwrap();
+        // ICONDIR header, then one 16-byte entry per image starting with its
+        // width and height
+        assert_eq!(bytes[..4], [0, 0, 1, 0]);
+        assert_eq!(u16::from_le_bytes([bytes[4], bytes[5]]), sizes.len() as u16);
+        for (i, size) in sizes.iter().enumerate() {
+            let entry = &bytes[6 + 16 * i..];
+            assert_eq!((u32::from(entry[0]), u32::from(entry[1])), (*size, *size));
+        }
+        let largest = image::load_from_memory(&bytes).unwrap();
+        assert_eq!((largest.width(), largest.height()), (48, 48));
+    }
+
+    #[test]
+    fn blank() {
+        // odd nibbles paint nothing
+        let builder =
+            IdenticonBuilder::new("21012146", 10, DEFAULT_BACKGROUND).with_hash_bytes([0xff; 16]);
+        let mut identicon = builder.build().unwrap();
+        assert!(identicon.is_blank());
+        identicon.set_accent(color::RGB::new(255, 0, 0));
+        assert!(!identicon.is_blank());
+
+        let builder =
+            IdenticonBuilder::new("21012146", 10, DEFAULT_BACKGROUND).with_hash_bytes([0xff; 16]);
+        assert!(!builder.avoid_blank(true).build().unwrap().is_blank());
+        assert!(!Identicon::new("21012146", 10, DEFAULT_BACKGROUND)
+            .unwrap()
+            .is_blank());
+
+        // a rule that never paints gives up instead of rehashing forever
+        let never = IdenticonBuilder::new("21012146", 10, DEFAULT_BACKGROUND)
+            .paint_rule(|_| false)
+            .avoid_blank(true)
+            .build()
+            .unwrap();
+        assert!(never.is_blank());
+        let plain = IdenticonBuilder::new("21012146", 10, DEFAULT_BACKGROUND)
+            .paint_rule(|_| false)
+            .build()
+            .unwrap();
+        assert_eq!(never.hash, plain.hash);
+        assert_eq!(never.foreground, plain.foreground);
+    }
+
+    #[test]
+    fn preview() {
+        let identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let scale = 3;
+        let preview = identicon.preview(scale).unwrap();
+        assert_eq!(preview.dimensions(), (7 * scale, 7 * scale));
+        for (x, y, pixel) in preview.enumerate_pixels() {
+            assert_eq!(
+                pixel,
+                preview.get_pixel(x / scale * scale, y / scale * scale)
+            );
+        }
+        // the blocks follow the grid
+        let grid = identicon.grid();
+        for (x, y, pixel) in preview.enumerate_pixels() {
+            let painted = grid[(y / scale) as usize][(x / scale) as usize];
+            assert_eq!(*pixel != DEFAULT_BACKGROUND.as_pixel(), painted);
+        }
+
+        let mut padded = identicon.clone();
+        padded.set_padding(10).unwrap();
+        padded.set_border(2, color::RGB::new(0, 0, 0)).unwrap();
+        let padded = padded.preview(scale).unwrap();
+        assert_eq!(padded.dimensions(), (7 * scale, 7 * scale));
+        assert_eq!(padded, preview);
+        assert!(matches!(identicon.preview(u32::MAX), Err(Error::TooLarge)));
+    }
+
+    #[test]
+    fn content_name() {
+        let identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let same = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let other = Identicon::new("21012147", 10, DEFAULT_BACKGROUND).unwrap();
+
+        let name = identicon.content_name(OutputFormat::Png);
+        let hash: HashBytes = Md5::digest("21012146").into();
+        assert_eq!(name, format!("{:032x}.png", u128::from_be_bytes(hash)));
+        assert_eq!(name, same.content_name(OutputFormat::Png));
+        assert_ne!(name, other.content_name(OutputFormat::Png));
+        assert!(identicon
+            .content_name(OutputFormat::Jpeg)
+            .ends_with(".jpeg"));
+    }
+
+    #[test]
+    fn shadow() {
+        let mut identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let plain = identicon.image();
+        let shadow = color::RGB::new(0, 0, 0);
+        identicon.set_shadow(2, 3, shadow.clone());
+        let img = identicon.image();
+        assert_eq!(img.dimensions(), plain.dimensions());
+
+        let foreground = identicon.foreground.as_pixel();
+        let mut shadowed = 0;
+        for (x, y, pixel) in plain.enumerate_pixels() {
+            if *pixel == foreground {
+                // cells stay on top of the shadow
+                assert_eq!(*img.get_pixel(x, y), foreground);
+                if let Some(below) = img.get_pixel_checked(x + 2, y + 3) {
+                    assert!(*below == foreground || *below == shadow.as_pixel());
+                    shadowed += usize::from(*below == shadow.as_pixel());
+                }
+            }
+        }
+        assert!(shadowed > 0);
+
+        // offsets past the edges are clipped rather than wrapped or panicking
+        identicon.set_shadow(-1000, 1000, shadow);
+        assert_eq!(identicon.image(), plain);
+    }
+
+    #[test]
+    fn rows_match_image() {
+        let mut identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let check = |identicon: &Identicon| {
+            let img = identicon.image();
+            let pixels: Vec<u8> = identicon.rows().flatten().flat_map(|p| p.0).collect();
+            assert_eq!(identicon.rows().count(), img.height() as usize);
+            assert_eq!(pixels, img.into_raw());
+        };
+        check(&identicon);
+
+        identicon.set_border(3, color::RGB::new(0, 0, 0)).unwrap();
+        identicon.set_padding(2).unwrap();
+        identicon.set_jitter(4);
+        identicon.set_shadow(-2, 3, color::RGB::new(90, 90, 90));
+        identicon.set_retro(true);
+        check(&identicon);
+        identicon.set_layout(Layout::HexGrid);
+        check(&identicon);
+    }
+
+    #[test]
+    fn pulse_gif() {
+        let identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let bytes = identicon.animated_pulse_gif(6, 80, 0.5, 1.0).unwrap();
+        let frames = GifDecoder::new(bytes.as_slice())
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+        assert_eq!(frames.len(), 6);
+        for frame in &frames {
+            assert_eq!(frame.buffer().dimensions(), identicon.dimensions());
+            assert_eq!(frame.delay().numer_denom_ms(), (80, 1));
+        }
+        // fully grown halfway through, shrunk at the start
+        let full = image::DynamicImage::ImageRgb8(identicon.image()).into_rgba8();
+        assert_eq!(*frames[3].buffer(), full);
+        assert_ne!(*frames[0].buffer(), full);
+    }
+
+    #[test]
+    fn custom_paint_rule() {
+        // nibbles 0 to 15 in order, then zeros
+        let mut hash = [0u8; 16];
+        for (i, byte) in hash.iter_mut().take(8).enumerate() {
+            *byte = (2 * i as u8) << 4 | (2 * i as u8 + 1);
+        }
+        let identicon = IdenticonBuilder::new("21012146", 10, DEFAULT_BACKGROUND)
+            .with_hash_bytes(hash)
+            .paint_rule(|n| n > 7)
+            .build()
+            .unwrap();
+        let mut expected = [false; NUM_PAINTS];
+        for nibble in 8..NUM_PAINTS {
+            expected[Identicon::nibble_index(nibble, NUM_SQUARES)] = true;
+        }
+        assert_eq!(identicon.paints, expected);
+
+        // the default rule is unchanged
+        let default = IdenticonBuilder::new("21012146", 10, DEFAULT_BACKGROUND)
+            .paint_rule(|n| n % 2 == 0)
+            .build()
+            .unwrap();
+        let identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        assert_eq!(identicon.paints, default.paints);
+    }
+
+    #[test]
+    fn ppm() {
+        let mut identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let ppm = identicon.to_ppm();
+        let header = b"P6\n70 70\n255\n";
+        assert_eq!(&ppm[..header.len()], header);
+
+        let grid = identicon.grid();
+        let pixels = &ppm[header.len()..];
+        assert_eq!(pixels.len(), 70 * 70 * 3);
+        for (i, pixel) in pixels.chunks(3).enumerate() {
+            let (x, y) = (i % 70, i / 70);
+            let color = if grid[y / 10][x / 10] {
+                &identicon.foreground
+            } else {
+                &DEFAULT_BACKGROUND
+            };
+            assert_eq!(pixel, color.as_pixel().0);
+        }
+        // plain squares match the regular rendering, decorations included
+        identicon.set_accent(color::RGB::new(255, 0, 0));
+        identicon.set_padding(2).unwrap();
+        identicon.set_border(3, color::RGB::new(0, 0, 0)).unwrap();
+        let ppm = identicon.to_ppm();
+        let header = b"P6\n80 80\n255\n";
+        assert_eq!(&ppm[..header.len()], header);
+        assert_eq!(&ppm[header.len()..], identicon.image().as_raw().as_slice());
+    }
+
+    #[test]
+    fn algorithm_v1_golden() {
+        let identicon = IdenticonBuilder::new("21012146", 10, DEFAULT_BACKGROUND)
+            .algorithm(Algorithm::V1)
+            .build()
+            .unwrap();
+        let bits = identicon
+            .paints
+            .iter()
+            .enumerate()
+            .fold(0u32, |bits, (i, paint)| bits | u32::from(*paint) << i);
+        assert_eq!(bits, 0x7ac4);
+        assert_eq!(identicon.foreground, color::RGB::new(218, 126, 214));
+
+        let default = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        assert!(default.collides_with(&identicon));
+    }
+
+    #[test]
+    fn light_on_dark() {
+        let dark = color::RGB::new(40, 40, 48);
+        let build = |light_on_dark| {
+            IdenticonBuilder::new("21012146", 10, dark.clone())
+                .light_on_dark(light_on_dark)
+                .build()
+                .unwrap()
+        };
+        let (plain, light) = (build(false), build(true));
+        assert!(light.foreground_hsl().lum() >= f32::from(DARK_BG_LUM_MIN) - 1.0);
+        assert!(light.foreground.relative_luminance() > plain.foreground.relative_luminance());
+        assert!(light.foreground.contrast_ratio(&dark) > plain.foreground.contrast_ratio(&dark));
+
+        // light backgrounds keep the usual foreground
+        let default = IdenticonBuilder::new("21012146", 10, DEFAULT_BACKGROUND)
+            .light_on_dark(true)
+            .build()
+            .unwrap();
+        let identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        assert_eq!(default.foreground, identicon.foreground);
+    }
+
+    #[test]
+    fn center_weighted() {
+        let build = |name: &str| {
+            IdenticonBuilder::new(name, 10, DEFAULT_BACKGROUND)
+                .center_weighted(true)
+                .build()
+                .unwrap()
+        };
+        let center = usize::from(NUM_SQUARES / 2);
+        let mut counts = [0; GRID_SIZE];
+        for n in 0..200 {
+            let name = n.to_string();
+            let identicon = build(&name);
+            let even = identicon.hash[CENTER_BYTE].is_multiple_of(2);
+            let grid = identicon.grid();
+            for cells in &grid[1..GRID_SIZE - 1] {
+                assert_eq!(cells[center], even, "{}", name);
+                for (count, painted) in counts.iter_mut().zip(cells) {
+                    *count += usize::from(*painted);
+                }
+            }
+        }
+        // edge cells are painted less often than those next to the center
+        assert!(counts[1] < counts[2], "{:?}", counts);
+        assert_eq!(counts[1], counts[5]);
+
+        // a name whose byte is even gets the full center column
+        let name = (0..)
+            .map(|n: u32| n.to_string())
+            .find(|name| build(name).hash[CENTER_BYTE].is_multiple_of(2))
+            .unwrap();
+        let grid = build(&name).grid();
+        assert!((1..GRID_SIZE - 1).all(|row| grid[row][center]));
+    }
+
+    #[test]
+    fn suppress_corners() {
+        let build = |name: &str, suppress| {
+            IdenticonBuilder::new(name, 10, DEFAULT_BACKGROUND)
+                .suppress_corners(suppress)
+                .build()
+                .unwrap()
+                .grid()
+        };
+        let last = GRID_SIZE - 2;
+        let corners = [(1, 1), (1, last), (last, 1), (last, last)];
+        let name = (0..)
+            .map(|n: u32| n.to_string())
+            .find(|name| {
+                let grid = build(name, false);
+                corners.iter().all(|&(row, col)| grid[row][col])
+            })
+            .unwrap();
+        let (plain, suppressed) = (build(&name, false), build(&name, true));
+        for (row, col) in corners {
+            assert!(!suppressed[row][col], "({}, {})", row, col);
+        }
+        // the other cells are left alone
+        for row in 0..GRID_SIZE {
+            for col in 0..GRID_SIZE {
+                if !corners.contains(&(row, col)) {
+                    assert_eq!(suppressed[row][col], plain[row][col]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn derived_background() {
+        for name in ["21012146", "alice", "bob"] {
+            let identicon = IdenticonBuilder::new(name, 10, DEFAULT_BACKGROUND)
+                .derived_background(true)
+                .build()
+                .unwrap();
+            let (fg, bg) = (identicon.foreground_hsl(), identicon.background.as_hsl());
+            let d = (fg.hue() - bg.hue()).rem_euclid(360.0);
+            assert!(d.min(360.0 - d) < 8.0, "{} {}", fg.hue(), bg.hue());
+            assert!(bg.lum() > fg.lum() + 15.0);
+            assert!(bg.sat() < fg.sat());
+        }
+        let plain = IdenticonBuilder::new("21012146", 10, DEFAULT_BACKGROUND)
+            .build()
+            .unwrap();
+        assert_eq!(plain.background, DEFAULT_BACKGROUND);
+    }
+
+    #[test]
+    fn auto_style() {
+        let build = |name: &str| {
+            let identicon = IdenticonBuilder::new(name, 10, DEFAULT_BACKGROUND)
+                .style(Style::Auto)
+                .build()
+                .unwrap();
+            let style = Style::Auto.resolve(&identicon.hash);
+            (style, identicon.shape, identicon.layout, identicon.image())
+        };
+        let (style, shape, layout, img) = build("21012146");
+        assert_eq!(build("21012146"), (style, shape, layout, img));
+
+        let styles: HashSet<Style> = (0..20).map(|n| build(&n.to_string()).0).collect();
+        assert!(styles.len() > 1);
+        assert!(!styles.contains(&Style::Auto));
+        for n in 0..20 {
+            let (style, shape, layout, _) = build(&n.to_string());
+            let expected = match style {
+                Style::Circle => (TileShape::Circle, Layout::Grid),
+                Style::Rounded => (TileShape::RoundedSquare { radius: 2 }, Layout::Grid),
+                Style::Hex => (TileShape::Square, Layout::HexGrid),
+                _ => (TileShape::Square, Layout::Grid),
+            };
+            assert_eq!((shape, layout), expected);
+        }
+    }
+
+    #[test]
+    fn hue_fn() {
+        let identicon = IdenticonBuilder::new("21012146", 10, DEFAULT_BACKGROUND)
+            .hue_fn(|_| 200.0)
+            .build()
+            .unwrap();
+        assert!((identicon.foreground_hsl().hue() - 200.0).abs() < 1.0);
+
+        // hues outside the wheel wrap around, and the hash still drives the rest
+        let wrapped = IdenticonBuilder::new("21012146", 10, DEFAULT_BACKGROUND)
+            .hue_fn(|_| 560.0)
+            .build()
+            .unwrap();
+        assert_eq!(wrapped.foreground, identicon.foreground);
+        let plain = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        assert_eq!(identicon.paints, plain.paints);
+
+        for hue in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+            let built = IdenticonBuilder::new("21012146", 10, DEFAULT_BACKGROUND)
+                .hue_fn(move |_| hue)
+                .build();
+            assert!(matches!(
+                built,
+                Err(Error::InvalidHSL(color::Error::HSLOutOfBounds {
+                    name: "hue",
+                    ..
+                }))
+            ));
+        }
+    }
+
+    #[test]
+    fn collision_probability() {
+        assert_eq!(super::collision_probability(0), 0.0);
+        assert_eq!(super::collision_probability(1), 0.0);
+        let mut last = 0.0;
+        for n in [2, 10, 1_000, 10_000, 100_000] {
+            let p = super::collision_probability(n);
+            assert!(p > last && p < 1.0, "{} {}", n, p);
+            last = p;
+        }
+        assert_eq!(super::collision_probability(usize::MAX), 1.0);
+        // past the square root of the space, collisions become likely
+        assert!(super::collision_probability(100_000) > 0.5);
+    }
+
+    #[test]
+    fn pack_paints() {
+        for bits in 0..1 << NUM_PAINTS {
+            assert_eq!(paints_to_u16(&paints_from_u16(bits)), bits);
+        }
+        assert_eq!(paints_from_u16(0x8000), [false; NUM_PAINTS]);
+
+        let identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        assert_eq!(
+            paints_from_u16(paints_to_u16(&identicon.paints)),
+            identicon.paints
+        );
+    }
+
+    #[test]
+    fn png_reproducible() {
+        let identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let bytes = identicon.png_reproducible().unwrap();
+        assert_eq!(bytes, identicon.png_reproducible().unwrap());
+
+        let decoder = png::Decoder::new(bytes.as_slice());
+        let reader = decoder.read_info().unwrap();
+        let info = reader.info();
+        assert!(info.uncompressed_latin1_text.is_empty() && info.utf8_text.is_empty());
+        let decoded = image::load_from_memory(&bytes).unwrap().to_rgb8();
+        assert_eq!(decoded, identicon.image());
+
+        // golden checksum for the png crate version in Cargo.lock
+        let checksum: HashBytes = Md5::digest(&bytes).into();
+        assert_eq!(
+            format!("{:032x}", u128::from_be_bytes(checksum)),
+            "c51e6ebc033c5c650b80b56a54f440ad"
+        );
+    }
+
+    #[test]
+    fn palette_foreground() {
+        let palette = color::Palette(vec![
+            color::RGB::new(200, 30, 30),
+            color::RGB::new(30, 160, 60),
+            color::RGB::new(40, 70, 200),
+        ]);
+        for i in 0..50 {
+            let identicon = IdenticonBuilder::new(&i.to_string(), 10, DEFAULT_BACKGROUND)
+                .palette(palette.clone())
+                .build()
+                .unwrap();
+            assert!(palette.0.contains(&identicon.foreground));
+        }
+
+        let identicon = IdenticonBuilder::new("21012146", 10, DEFAULT_BACKGROUND)
+            .palette(color::Palette(Vec::new()))
+            .build()
+            .unwrap();
+        assert_eq!(identicon.foreground, color::RGB::new(218, 126, 214));
+    }
+
+    #[test]
+    fn ansi() {
+        let identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let ansi = identicon.to_ansi();
+        assert!(ansi.contains("\x1b[48;2;218;126;214m  "));
+        assert!(ansi.contains("\x1b[48;2;240;240;240m  "));
+        assert_eq!(ansi.lines().count(), GRID_SIZE);
+        assert!(ansi.lines().all(|line| line.ends_with("\x1b[0m")));
+        let painted = identicon.painted_cells().count();
+        assert_eq!(ansi.matches("\x1b[48;2;218;126;214m").count(), painted);
+    }
+
+    #[test]
+    fn domain() {
+        let build = |domain: Option<&str>| {
+            let builder = IdenticonBuilder::new("21012146", 10, DEFAULT_BACKGROUND);
+            match domain {
+                Some(domain) => builder.domain(domain),
+                None => builder,
+            }
+            .build()
+            .unwrap()
+        };
+        let v1 = build(Some("identicon-v1"));
+        assert_eq!(v1.hash, build(Some("identicon-v1")).hash);
+        assert_ne!(v1.hash, build(None).hash);
+        assert_ne!(v1.hash, build(Some("identicon-v2")).hash);
+
+        let mut hasher = Md5::new();
+        hasher.update((12 | 1u64 << 63).to_be_bytes());
+        hasher.update("identicon-v1");
+        hasher.update("21012146");
+        let expected: HashBytes = hasher.finalize().into();
+        assert_eq!(v1.hash, expected);
+
+        // a salt of the same value doesn't reproduce the domain
+        let salted = IdenticonBuilder::new("21012146", 10, DEFAULT_BACKGROUND)
+            .salt("identicon-v1")
+            .build()
+            .unwrap();
+        assert_ne!(salted.hash, v1.hash);
+    }
+
+    #[test]
+    fn image_is_memoized() {
+        let mut identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        assert!(identicon.rendered.0.get().is_none());
+        let png = image::load_from_memory(&identicon.bytes(OutputFormat::Png).unwrap()).unwrap();
+        let first: *const RgbImage = identicon.rendered();
+        identicon.bytes(OutputFormat::Gif).unwrap();
+        identicon.bytes(OutputFormat::Jpeg).unwrap();
+        // still the image built for the first format
+        assert!(std::ptr::eq(first, identicon.rendered()));
+        assert_eq!(png.to_rgb8(), identicon.image());
+
+        // setters drop the stale image
+        identicon.set_padding(3).unwrap();
+        assert!(identicon.rendered.0.get().is_none());
+        assert_eq!(identicon.image().dimensions(), identicon.dimensions());
+    }
+
+    #[test]
+    fn no_seams_between_cells() {
+        for size in [10, 13, 17] {
+            let builder =
+                IdenticonBuilder::new("21012146", size, DEFAULT_BACKGROUND).paint_rule(|_| true);
+            let identicon = builder.build().unwrap();
+            let img = identicon.image();
+            let fg = identicon.foreground.as_pixel();
+            // the painted 5x5 block inside the outer ring is solid
+            for y in size..6 * size {
+                for x in size..6 * size {
+                    assert_eq!(*img.get_pixel(x, y), fg, "size {} at ({}, {})", size, x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn png_reader() {
+        let identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let mut read = Vec::new();
+        identicon
+            .png_reader()
+            .unwrap()
+            .read_to_end(&mut read)
+            .unwrap();
+        let (png, _) = identicon.render_to_vec(OutputFormat::Png).unwrap();
+        assert_eq!(read, png);
+    }
+
+    #[test]
+    fn encode_all() {
+        let identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let formats = [OutputFormat::Png, OutputFormat::Jpeg, OutputFormat::Gif];
+        let encoded = identicon.encode_all(&formats).unwrap();
+        let first: *const RgbImage = identicon.rendered.0.get().unwrap();
+        assert_eq!(encoded.len(), formats.len());
+        for ((format, bytes), expected) in encoded.iter().zip(formats) {
+            assert_eq!(*format, expected);
+            let guessed = image::guess_format(bytes).unwrap();
+            assert_eq!(guessed.to_mime_type(), format.mime_type());
+            let img = image::load_from_memory(bytes).unwrap();
+            assert_eq!((img.width(), img.height()), identicon.dimensions());
+        }
+        assert!(std::ptr::eq(first, identicon.rendered()));
+    }
+
+    #[test]
+    fn similarity() {
+        let identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let same = Identicon::new("21012146", 10, color::RGB::new(0, 0, 0)).unwrap();
+        assert_eq!(identicon.similarity(&same), 1.0);
+
+        // even nibbles paint, so 0x0 and 0x1 nibbles give opposite patterns
+        let build = |hash, foreground| {
+            let mut identicon = IdenticonBuilder::new("", 10, DEFAULT_BACKGROUND)
+                .with_hash_bytes(hash)
+                .build()
+                .unwrap();
+            identicon.set_foreground(foreground);
+            identicon
+        };
+        let black = build([0x00; 16], color::RGB::new(0, 0, 0));
+        let white = build([0x11; 16], color::RGB::new(255, 255, 255));
+        assert!(white.similarity(&black) < 1e-6);
+        assert_eq!(white.similarity(&black), black.similarity(&white));
+
+        let other = Identicon::new("21012147", 10, DEFAULT_BACKGROUND).unwrap();
+        let score = identicon.similarity(&other);
+        assert!(score > 0.0 && score < 1.0);
+    }
+
+    #[test]
+    fn noisy_background() {
+        let mut identicon = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        let plain = identicon.image();
+        identicon.set_noisy_background(6);
+        let img = identicon.image();
+        // the same texture every time for the same name
+        let mut again = Identicon::new("21012146", 10, DEFAULT_BACKGROUND).unwrap();
+        again.set_noisy_background(6);
+        assert_eq!(img, again.image());
+
+        let background = DEFAULT_BACKGROUND.as_pixel();
+        let mut seen = HashSet::new();
+        for (x, y, pixel) in plain.enumerate_pixels() {
+            if *pixel == background {
+                let noisy = img.get_pixel(x, y);
+                for (c, base) in noisy.0.iter().zip(background.0) {
+                    assert!(i16::from(*c).abs_diff(i16::from(base)) <= 6);
+                }
+                seen.insert(noisy.0);
+            } else {
+                assert_eq!(img.get_pixel(x, y), pixel);
+            }
         }
+        assert!(seen.len() > 1);
     }
 }
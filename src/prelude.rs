@@ -0,0 +1,18 @@
+//! The types most callers need, for a single glob import:
+//!
+//! ```
+//! use identicon::prelude::*;
+//!
+//! fn avatar(name: &str) -> Result<String> {
+//!     let mut identicon = Identicon::new(name, 10, RGB::new(240, 240, 240))?;
+//!     identicon.set_foreground(HSL::new(200.0, 60.0, 50.0)?.as_rgb());
+//!     identicon.encode(OutputFormat::Png)
+//! }
+//!
+//! assert!(avatar("21012146").unwrap().starts_with("iVBOR"));
+//! let err: Error = HSL::new(400.0, 0.0, 0.0).err().unwrap().into();
+//! assert!(err.is_color());
+//! ```
+
+pub use crate::color::{HSL, RGB};
+pub use crate::{Error, Identicon, OutputFormat, Result};
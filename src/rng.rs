@@ -0,0 +1,86 @@
+// xorshift128+ generator; deterministic for a given seed, not cryptographically secure
+pub struct Xorshift {
+    state: [u64; 2],
+    word: [u8; 8],
+    pos: usize,
+}
+
+// used in place of an all-zero seed, which would only ever produce zeros
+const FALLBACK_SEED: u64 = 0x9e3779b97f4a7c15;
+
+impl Xorshift {
+    pub fn new(seed: &[u8; 16]) -> Self {
+        let mut low = [0u8; 8];
+        let mut high = [0u8; 8];
+        low.copy_from_slice(&seed[..8]);
+        high.copy_from_slice(&seed[8..]);
+        let mut state = [u64::from_le_bytes(low), u64::from_le_bytes(high)];
+        if state == [0, 0] {
+            state[0] = FALLBACK_SEED;
+        }
+
+        Self {
+            state,
+            word: [0; 8],
+            pos: 8,
+        }
+    }
+
+    fn next_word(&mut self) -> u64 {
+        let [mut s1, s0] = self.state;
+        s1 ^= s1 << 23;
+        s1 ^= s1 >> 17;
+        s1 ^= s0 ^ (s0 >> 26);
+        self.state = [s0, s1];
+        s0.wrapping_add(s1)
+    }
+}
+
+// splitmix64's finalizer: a stateless scramble of value, for random-looking
+// values keyed by position rather than drawn in sequence
+pub fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(FALLBACK_SEED);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+impl Iterator for Xorshift {
+    type Item = u8;
+
+    // bytes of each 64-bit output, least significant first
+    fn next(&mut self) -> Option<u8> {
+        if self.pos == self.word.len() {
+            self.word = self.next_word().to_le_bytes();
+            self.pos = 0;
+        }
+        self.pos += 1;
+        Some(self.word[self.pos - 1])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mix, Xorshift};
+
+    #[test]
+    fn deterministic() {
+        let seed = [7u8; 16];
+        let a: Vec<u8> = Xorshift::new(&seed).take(32).collect();
+        let b: Vec<u8> = Xorshift::new(&seed).take(32).collect();
+        assert_eq!(a, b);
+        assert_ne!(a, Xorshift::new(&[8u8; 16]).take(32).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn zero_seed() {
+        assert!(Xorshift::new(&[0u8; 16]).take(16).any(|b| b != 0));
+    }
+
+    #[test]
+    fn mix_scrambles() {
+        assert_eq!(mix(42), mix(42));
+        assert_ne!(mix(0), mix(1));
+        assert_ne!(mix(0), 0);
+    }
+}
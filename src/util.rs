@@ -0,0 +1,29 @@
+// linearly map val in [vmin, vmax] to [dmin, dmax]; values outside the
+// source range extrapolate, and a degenerate source range (vmin == vmax)
+// maps everything to dmin instead of dividing by zero
+pub fn map(val: f32, vmin: f32, vmax: f32, dmin: f32, dmax: f32) -> f32 {
+    if vmin == vmax {
+        return dmin;
+    }
+    dmin + ((val - vmin) * (dmax - dmin)) / (vmax - vmin)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::map;
+
+    #[test]
+    fn linear() {
+        assert_eq!(map(0.0, 0.0, 255.0, 0.0, 100.0), 0.0);
+        assert_eq!(map(255.0, 0.0, 255.0, 0.0, 100.0), 100.0);
+        assert_eq!(map(5.0, 0.0, 10.0, 20.0, 40.0), 30.0);
+        assert_eq!(map(5.0, 0.0, 10.0, 40.0, 20.0), 30.0);
+        assert_eq!(map(0.0, 0.0, 255.0, 65.0, 45.0), 65.0);
+    }
+
+    #[test]
+    fn degenerate() {
+        assert_eq!(map(3.0, 1.0, 1.0, 7.0, 9.0), 7.0);
+        assert!(map(3.0, 1.0, 1.0, 7.0, 9.0).is_finite());
+    }
+}
@@ -0,0 +1,253 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+    run_with_env(args, &[])
+}
+
+fn run_with_env(args: &[&str], vars: &[(&str, &str)]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_cli"))
+        .args(args)
+        .env_remove("IDENTICON_BACKGROUND")
+        .env_remove("IDENTICON_FOREGROUND")
+        .envs(vars.iter().copied())
+        .output()
+        .expect("failed to run cli")
+}
+
+// a fresh scratch directory per test so parallel tests don't clash
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("identicon-cli-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn padding_grows_dimensions() {
+    let dir = scratch_dir("padding");
+    let plain = dir.join("plain.png");
+    let padded = dir.join("padded.png");
+    let output = run(&["21012146", "-s", "10", "render", plain.to_str().unwrap()]);
+    assert!(output.status.success());
+    let output = run(&[
+        "21012146",
+        "-s",
+        "10",
+        "--padding",
+        "10",
+        "render",
+        padded.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+
+    let (width, height) = image::image_dimensions(&plain).unwrap();
+    assert_eq!(
+        image::image_dimensions(&padded).unwrap(),
+        (width + 20, height + 20)
+    );
+}
+
+#[test]
+fn oversized_padding_fails() {
+    for args in [
+        [
+            "21012146",
+            "-s",
+            "10",
+            "--padding",
+            "4000000000",
+            "encode",
+            "png",
+        ],
+        [
+            "21012146",
+            "-s",
+            "10",
+            "--border",
+            "4000000000:0,0,0",
+            "encode",
+            "png",
+        ],
+        [
+            "21012146",
+            "-s",
+            "613566756",
+            "--padding",
+            "613566756",
+            "encode",
+            "png",
+        ],
+    ] {
+        let output = run(&args);
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(!stderr.contains("panicked"), "{}", stderr);
+    }
+}
+
+#[test]
+fn render_unsupported_extension() {
+    let dir = scratch_dir("extension");
+    let path = dir.join("out.xyz");
+    let output = run(&["21012146", "render", path.to_str().unwrap()]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("unsupported file extension"), "{}", stderr);
+    assert!(stderr.contains("out.xyz"), "{}", stderr);
+    assert!(!path.exists());
+}
+
+#[test]
+fn colors_from_env() {
+    let from_flags = run(&["21012146", "-b", "1,2,3", "-f", "4,5,6", "encode", "png"]);
+    let from_env = run_with_env(
+        &["21012146", "encode", "png"],
+        &[
+            ("IDENTICON_BACKGROUND", "1,2,3"),
+            ("IDENTICON_FOREGROUND", "4,5,6"),
+        ],
+    );
+    let default = run(&["21012146", "encode", "png"]);
+    assert!(from_env.status.success());
+    assert_eq!(from_env.stdout, from_flags.stdout);
+    assert_ne!(from_env.stdout, default.stdout);
+
+    let flag_wins = run_with_env(
+        &["21012146", "-b", "240,240,240", "encode", "png"],
+        &[("IDENTICON_BACKGROUND", "1,2,3")],
+    );
+    assert_eq!(flag_wins.stdout, default.stdout);
+}
+
+#[test]
+fn seed_changes_output() {
+    let a = run(&["21012146", "--seed", "a", "encode", "png"]);
+    let b = run(&["21012146", "--seed", "b", "encode", "png"]);
+    assert!(a.status.success() && b.status.success());
+    assert_ne!(a.stdout, b.stdout);
+    assert_eq!(
+        a.stdout,
+        run(&["21012146", "--seed", "a", "encode", "png"]).stdout
+    );
+}
+
+#[test]
+fn render_multiple_formats() {
+    let dir = scratch_dir("formats");
+    let base = dir.join("out");
+    let output = run(&[
+        "21012146",
+        "render",
+        base.to_str().unwrap(),
+        "--format",
+        "png",
+        "--format",
+        "gif",
+    ]);
+    assert!(output.status.success());
+    let png = fs::read(dir.join("out.png")).unwrap();
+    let gif = fs::read(dir.join("out.gif")).unwrap();
+    assert_eq!(image::guess_format(&png).unwrap(), image::ImageFormat::Png);
+    assert_eq!(image::guess_format(&gif).unwrap(), image::ImageFormat::Gif);
+}
+
+#[test]
+fn encode_multiple_formats() {
+    let output = run(&["21012146", "encode", "png", "jpeg"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("base64 encoded png: "));
+    assert!(lines[1].starts_with("base64 encoded jpeg: "));
+}
+
+#[test]
+fn encode_quiet() {
+    let verbose = String::from_utf8(run(&["21012146", "encode", "png"]).stdout).unwrap();
+    let encoded = verbose.trim_end().strip_prefix("base64 encoded: ").unwrap();
+
+    let output = run(&["21012146", "encode", "png", "--quiet"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("{}\n", encoded)
+    );
+}
+
+#[test]
+fn completions_bash() {
+    let output = run(&["completions", "bash"]);
+    assert!(output.status.success());
+    let script = String::from_utf8(output.stdout).unwrap();
+    assert!(script.contains("complete -F _cli cli"));
+    assert!(script.contains("render"));
+}
+
+#[test]
+fn bench_prints_rate() {
+    let output = run(&["bench", "--count", "5", "--size", "2"]);
+    assert!(output.status.success());
+    let report = String::from_utf8(output.stdout).unwrap();
+    assert!(report.starts_with("5 identicons in "), "{}", report);
+    assert!(report.trim_end().ends_with("images/sec"), "{}", report);
+}
+
+#[test]
+fn json_output() {
+    let output = run(&["--json", "-s", "2", "21012146", "encode", "png"]);
+    assert!(output.status.success());
+    let json = String::from_utf8(output.stdout).unwrap();
+    let json = json.trim_end();
+    assert!(json.starts_with('{') && json.ends_with('}'));
+    assert!(json.contains("\"width\":14,\"height\":14"));
+    assert!(json.contains("\"foreground\":["));
+    assert!(json.contains("{\"format\":\"png\",\"base64\":\"iVBOR"));
+
+    // the flag is global, so it may follow the subcommand too
+    let after = run(&["-s", "2", "21012146", "encode", "png", "--json"]);
+    assert!(after.status.success());
+    assert_eq!(String::from_utf8(after.stdout).unwrap().trim_end(), json);
+}
+
+#[test]
+fn render_multiple_names() {
+    let dir = scratch_dir("names").join("out");
+    let output = run(&["alice", "bob", "carol", "render", dir.to_str().unwrap()]);
+    assert!(output.status.success(), "{:?}", output);
+    for name in ["alice", "bob", "carol"] {
+        let img = image::open(dir.join(format!("{}.png", name))).unwrap();
+        assert_eq!(img.width(), 420);
+    }
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 3);
+
+    // a file in the way of the directory fails like the library's render
+    let file = dir.join("alice.png");
+    let output = run(&["alice", "bob", "render", file.to_str().unwrap()]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("encounter error accessing path="),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn transparent_background() {
+    let dir = scratch_dir("transparent");
+    let path = dir.join("out.png");
+    let output = run(&["-b", "none", "21012146", "render", path.to_str().unwrap()]);
+    assert!(output.status.success(), "{:?}", output);
+    let img = image::open(&path).unwrap();
+    assert!(img.color().has_alpha());
+    assert_eq!(img.to_rgba8()[(0, 0)].0[3], 0);
+
+    let output = run(&["-b", "none", "21012146", "encode", "jpeg"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("jpeg has no alpha channel"));
+}